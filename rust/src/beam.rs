@@ -0,0 +1,169 @@
+use std::cmp::Reverse;
+
+use crate::calepinage::{
+    select_alternative_planks_for_line, Calepinage, CalepinageError, Deck, Line, Plank,
+    PlankHeap,
+};
+use crate::control::{Control, Outcome};
+use crate::rules::Rules;
+use crate::score::Weights;
+
+// A partial calepinage and the planks left to finish it.
+#[derive(Debug, Clone)]
+struct BeamState {
+    calepinage: Calepinage,
+    remaining: PlankHeap,
+}
+
+impl BeamState {
+    // The more distinct lengths are left, the easier the next lines are to fill.
+    fn distinct_lengths(&self) -> usize {
+        let mut lengths: Vec<usize> = self.remaining.planks().iter().map(|plank| plank.length).collect();
+        lengths.dedup();
+        lengths.len()
+    }
+}
+
+/// Lays the deck line by line keeping the `beam_width` best partial calepinages after each line,
+/// so a line using the planks the next lines need does not end the search.
+/// A wider beam is slower but fails less often.
+pub fn calepine_with_beam(
+    plank_heap: PlankHeap,
+    deck: Deck,
+    rules: &Rules,
+    beam_width: usize,
+) -> Result<Calepinage, CalepinageError> {
+    calepine_with_beam_and_control(plank_heap, deck, rules, beam_width, &mut Control::default())
+        .map(|outcome| outcome.calepinage)
+}
+
+/// Like `calepine_with_beam`, checking `control` and reporting progress after each line.
+/// Among the partial calepinages leaving as many distinct lengths, the beam keeps those with
+/// the best score under the weights of `control`; the outcome is the best of the last beam,
+/// partial when interrupted.
+pub fn calepine_with_beam_and_control(
+    plank_heap: PlankHeap,
+    deck: Deck,
+    rules: &Rules,
+    beam_width: usize,
+    control: &mut Control,
+) -> Result<Outcome, CalepinageError> {
+    let mut planks: Vec<Plank> = plank_heap.planks().to_vec();
+    planks.sort_by_key(|plank| Reverse(plank.length));
+    let mut beam = vec![BeamState {
+        calepinage: Calepinage::default(),
+        remaining: PlankHeap::from_planks(planks),
+    }];
+
+    for line_index in 0..deck.width {
+        if control.is_interrupted() {
+            return Ok(Outcome {
+                calepinage: best_of(beam, &plank_heap, control.weights()),
+                interrupted: true,
+            });
+        }
+        let mut first_error = None;
+        let mut next_beam: Vec<BeamState> = vec![];
+        for state in &beam {
+            let is_forbidden = rules.forbidden_junctions(&state.calepinage);
+            let steps = match select_alternative_planks_for_line(
+                &state.remaining,
+                deck.length,
+                &is_forbidden,
+            ) {
+                Ok(steps) => steps,
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                    continue;
+                }
+            };
+            for step in steps {
+                let line = Line(step.selected.planks().to_vec());
+                let is_repeated = rules
+                    .repetition
+                    .as_ref()
+                    .is_some_and(|repetition| repetition.repeats(&line, &state.calepinage));
+                if is_repeated {
+                    first_error.get_or_insert(CalepinageError::RepeatedLine(line_index));
+                    continue;
+                }
+                let calepinage = state.calepinage.clone().with_line(line);
+                if next_beam.iter().all(|other| other.calepinage.lengths() != calepinage.lengths()) {
+                    next_beam.push(BeamState {
+                        calepinage,
+                        remaining: step.remaining,
+                    });
+                }
+            }
+        }
+        if next_beam.is_empty() {
+            return Err(first_error.unwrap_or(CalepinageError::NotEnoughPlanks));
+        }
+        let weights = control.weights();
+        let mut ranked: Vec<(usize, f64, BeamState)> = next_beam
+            .into_iter()
+            .map(|state| (state.distinct_lengths(), weights.evaluate(&state.calepinage, &plank_heap), state))
+            .collect();
+        ranked.sort_by(|(lhs_lengths, lhs_score, _), (rhs_lengths, rhs_score, _)| {
+            rhs_lengths.cmp(lhs_lengths).then(lhs_score.total_cmp(rhs_score))
+        });
+        let mut next_beam: Vec<BeamState> = ranked.into_iter().map(|(_, _, state)| state).collect();
+        next_beam.truncate(beam_width.max(1));
+        beam = next_beam;
+        control.report_layout(&beam[0].calepinage, &plank_heap);
+    }
+
+    Ok(Outcome {
+        calepinage: best_of(beam, &plank_heap, control.weights()),
+        interrupted: false,
+    })
+}
+
+fn best_of(beam: Vec<BeamState>, plank_heap: &PlankHeap, weights: &Weights) -> Calepinage {
+    beam.into_iter()
+        .map(|state| (weights.evaluate(&state.calepinage, plank_heap), state.calepinage))
+        .min_by(|(lhs, _), (rhs, _)| lhs.total_cmp(rhs))
+        .map(|(_, calepinage)| calepinage)
+        .unwrap_or_default()
+}
+
+#[test]
+fn wider_beam_should_find_layout_greedy_misses() {
+    use crate::calepinage::calepine;
+
+    let deck = Deck::new(12, 3).unwrap();
+    let plank_heap = || {
+        PlankHeap::default()
+            .add(2, 10)
+            .add(1, 9)
+            .add(1, 7)
+            .add(1, 6)
+            .add(1, 4)
+            .add(2, 2)
+            .add(1, 1)
+    };
+
+    assert!(calepine(plank_heap(), deck.clone()).is_err());
+    assert!(calepine_with_beam(plank_heap(), deck.clone(), &Rules::default(), 1).is_err());
+    let calepinage = calepine_with_beam(plank_heap(), deck, &Rules::default(), 4).unwrap();
+    assert!(Rules::default().accept(&calepinage));
+    assert_eq!(3, calepinage.0.len());
+}
+
+#[test]
+fn beam_should_rank_layouts_with_the_weights_of_its_control() {
+    let deck = Deck::new(5, 2).unwrap();
+    let plank_heap = PlankHeap::default().add(2, 4).add(2, 2).add(2, 1);
+    let layout = |weights: Weights| {
+        let mut control = Control::default().with_weights(weights);
+        calepine_with_beam_and_control(plank_heap.clone(), deck.clone(), &Rules::default(), 8, &mut control)
+            .unwrap()
+            .calepinage
+    };
+
+    let staggered = layout(Weights::default());
+    let closer = layout(Weights::default().with_stagger(-1.0));
+
+    assert_eq!(Some(3), crate::metrics::min_stagger(&staggered));
+    assert_eq!(Some(1), crate::metrics::min_stagger(&closer));
+}
@@ -1,5 +1,9 @@
+use std::cmp::Reverse;
 use std::collections::HashSet;
+#[cfg(test)]
 use spectral::assert_that;
+use crate::control::{Control, Outcome};
+use crate::rules::{RepetitionRule, Rules};
 
 // This is a deck with length = 6 and width = 4
 // It's made with 8 planks.
@@ -34,9 +38,19 @@ impl Deck {
     }
 }
 
+/// Where a board comes from, for planks that must be traced back to the inventory.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct PlankMetadata {
+    pub batch: Option<String>,
+    pub supplier: Option<String>,
+    pub grade: Option<String>,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Plank {
     pub length: usize,
+    pub id: Option<String>,
+    pub metadata: PlankMetadata,
 }
 
 impl Plank {
@@ -46,12 +60,53 @@ impl Plank {
         if length > Self::MAX_LENGTH {
             Err(format!("max length of plank is {}", Self::MAX_LENGTH))
         } else {
-            Ok(Plank { length })
+            Ok(Plank {
+                length,
+                id: None,
+                metadata: PlankMetadata::default(),
+            })
+        }
+    }
+
+    pub fn with_id(self, id: &str) -> Self {
+        Plank {
+            id: Some(id.to_string()),
+            ..self
+        }
+    }
+
+    pub fn with_batch(self, batch: &str) -> Self {
+        Plank {
+            metadata: PlankMetadata {
+                batch: Some(batch.to_string()),
+                ..self.metadata
+            },
+            ..self
+        }
+    }
+
+    pub fn with_supplier(self, supplier: &str) -> Self {
+        Plank {
+            metadata: PlankMetadata {
+                supplier: Some(supplier.to_string()),
+                ..self.metadata
+            },
+            ..self
+        }
+    }
+
+    pub fn with_grade(self, grade: &str) -> Self {
+        Plank {
+            metadata: PlankMetadata {
+                grade: Some(grade.to_string()),
+                ..self.metadata
+            },
+            ..self
         }
     }
 }
 
-#[derive(Default, Debug, PartialEq)]
+#[derive(Default, Debug, PartialEq, Clone)]
 pub struct PlankHeap {
     planks: Vec<Plank>,
     total_length: usize,
@@ -76,12 +131,27 @@ impl PlankHeap {
         }
     }
 
+    /// Adds a plank as is, keeping its id and metadata.
+    pub fn add_plank(self, plank: Plank) -> Self {
+        let total_length = self.total_length + plank.length;
+        let mut planks = self.planks;
+        planks.push(plank);
+        PlankHeap { planks, total_length }
+    }
+
     pub fn from_planks(planks: Vec<Plank>) -> Self {
-        planks
-            .iter()
-            .fold(PlankHeap::new(), |heap, plank| heap.add(1, plank.length))
+        planks.into_iter().fold(PlankHeap::new(), PlankHeap::add_plank)
+    }
+
+    pub fn planks(&self) -> &[Plank] {
+        &self.planks
+    }
+
+    pub fn total_length(&self) -> usize {
+        self.total_length
     }
 
+    #[allow(clippy::inherent_to_string)]
     fn to_string(&self) -> String {
         self.planks.iter().map(|p| p.length.to_string()).collect::<Vec<String>>().join(", ")
     }
@@ -109,15 +179,26 @@ impl Line {
         Line(planks)
     }
 
+    pub fn lengths(&self) -> Vec<usize> {
+        self.0.iter().map(|plank| plank.length).collect()
+    }
+
+    /// Whether both lines have the same sequence of plank lengths, as is or mirrored.
+    pub fn looks_like(&self, other: &Line) -> bool {
+        let lengths = self.lengths();
+        let other_lengths = other.lengths();
+        lengths == other_lengths || lengths.iter().eq(other_lengths.iter().rev())
+    }
+
     pub fn compute_junction(&self) -> Vec<Junction> {
         if self.0.len() > 1 {
             self.0
                 .iter()
                 .scan(0, |acc, plank| {
-                    *acc = *acc + plank.length;
+                    *acc += plank.length;
                     Some(*acc)
                 })
-                .map(|j| Junction(j))
+                .map(Junction)
                 .take(self.0.len() - 1)
                 .collect()
         } else {
@@ -128,7 +209,18 @@ impl Line {
 
 /// A Junction is a coordinate in a 1 dimension plan corresponding to two plank edges
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
-pub struct Junction(usize);
+pub struct Junction(pub(crate) usize);
+
+impl Junction {
+    /// Distance from the start of the line.
+    pub fn position(&self) -> usize {
+        self.0
+    }
+
+    pub fn distance(&self, other: &Junction) -> usize {
+        self.0.abs_diff(other.0)
+    }
+}
 
 #[test]
 fn empty_line_should_have_no_junction() {
@@ -187,6 +279,40 @@ impl Calepinage {
         lines.push(new_line_to_add);
         Calepinage(lines)
     }
+
+    /// Lengths of each line: layouts are told apart by them, planks of a length being
+    /// interchangeable whatever their id or metadata.
+    pub fn lengths(&self) -> Vec<Vec<usize>> {
+        self.0.iter().map(Line::lengths).collect()
+    }
+
+    /// Line and position on the line, both from 0, of the plank with the given id.
+    pub fn locate(&self, id: &str) -> Option<(usize, usize)> {
+        self.0.iter().enumerate().find_map(|(line_index, line)| {
+            line.0
+                .iter()
+                .position(|plank| plank.id.as_deref() == Some(id))
+                .map(|position| (line_index, position))
+        })
+    }
+}
+
+#[test]
+fn calepine_should_keep_plank_ids_and_metadata() {
+    let deck = Deck::new(5, 2).unwrap();
+    let plank_heap = PlankHeap::default()
+        .add_plank(Plank::new(3).unwrap().with_id("B-0410").with_batch("2021-06"))
+        .add_plank(Plank::new(2).unwrap().with_id("B-0411"))
+        .add_plank(Plank::new(3).unwrap().with_id("B-0412").with_supplier("Scierie du Rhône").with_grade("A"))
+        .add_plank(Plank::new(2).unwrap().with_id("B-0413"));
+
+    let calepinage = calepine(plank_heap, deck).unwrap();
+
+    let Calepinage(lines) = &calepinage;
+    assert_eq!(Some((1, 1)), calepinage.locate("B-0412"));
+    assert_eq!(Some("A"), lines[1].0[1].metadata.grade.as_deref());
+    assert_eq!(Some("2021-06"), lines[0].0[0].metadata.batch.as_deref());
+    assert_eq!(None, calepinage.locate("B-9999"));
 }
 
 #[test]
@@ -202,14 +328,15 @@ fn with_line_should_append_lines_in_order() {
 
 #[derive(Default, Debug, PartialEq)]
 pub struct CalepineStep {
-    remaining: PlankHeap,
-    selected: PlankHeap,
-    stash: Option<Plank>,
+    pub(crate) remaining: PlankHeap,
+    pub(crate) selected: PlankHeap,
+    stash: PlankHeap,
 }
 
 impl CalepineStep {
+    #[allow(clippy::inherent_to_string)]
     fn to_string(&self) -> String {
-        format!("remaining = [{}], selected = [{}], stash = {:?}", self.remaining.to_string(), self.selected.to_string(), self.stash )
+        format!("remaining = [{}], selected = [{}], stash = [{}]", self.remaining.to_string(), self.selected.to_string(), self.stash.to_string())
     }
 }
 
@@ -217,60 +344,304 @@ impl CalepineStep {
 pub enum CalepinageError {
     NotEnoughPlanks,
     OnlyUnusablePlanksRemaining(String),
+    RepeatedLine(usize),
+    /// Proven: no layout of these planks follows the rules.
+    Infeasible,
+    /// An external solver failed without answering.
+    Solver(String),
 }
 
 pub fn calepine(plank_heap: PlankHeap, deck: Deck) -> Result<Calepinage, CalepinageError> {
-    let mut the_plank_heap: PlankHeap = PlankHeap::from_planks(plank_heap.planks);
+    calepine_with_rules(plank_heap, deck, &Rules::default())
+}
+
+pub fn calepine_with_rules(
+    plank_heap: PlankHeap,
+    deck: Deck,
+    rules: &Rules,
+) -> Result<Calepinage, CalepinageError> {
+    calepine_with_control(plank_heap, deck, rules, &mut Control::default()).map(|outcome| outcome.calepinage)
+}
+
+/// Like `calepine_with_rules`, checking `control` and reporting progress after each line.
+pub fn calepine_with_control(
+    plank_heap: PlankHeap,
+    deck: Deck,
+    rules: &Rules,
+    control: &mut Control,
+) -> Result<Outcome, CalepinageError> {
+    let mut the_plank_heap: PlankHeap = PlankHeap::from_planks(plank_heap.planks.clone());
     let decreasing_length = |a: &Plank, b: &Plank| b.length.cmp(&a.length);
     the_plank_heap.planks.sort_by(decreasing_length);
 
     let mut calepinage = Calepinage::default();
     for _ in 0..deck.width {
-        let previous_line_junctions = calepinage.0.last().map_or_else(|| HashSet::new(), |line| line.compute_junction().into_iter().collect());
+        if control.is_interrupted() {
+            return Ok(Outcome {
+                calepinage,
+                interrupted: true,
+            });
+        }
+        let is_forbidden = rules.forbidden_junctions(&calepinage);
         let CalepineStep {
             selected: result,
             remaining: next_remaining,
             stash: _,
-        } = select_planks_for_line(&mut the_plank_heap, deck.length, previous_line_junctions)?;
+        } = match &rules.repetition {
+            None => select_planks_for_line(&the_plank_heap, deck.length, &is_forbidden)?,
+            Some(repetition) => select_planks_for_unrepeated_line(
+                &the_plank_heap,
+                deck.length,
+                &is_forbidden,
+                repetition,
+                &calepinage,
+            )?,
+        };
         the_plank_heap = next_remaining;
         calepinage = calepinage.with_line(Line(result.planks));
+        control.report_layout(&calepinage, &plank_heap);
     }
 
-    Ok(calepinage)
+    Ok(Outcome {
+        calepinage,
+        interrupted: false,
+    })
 }
 
-fn select_planks_for_line(
-    the_plank_heap: &mut PlankHeap,
+pub(crate) fn select_planks_for_line(
+    the_plank_heap: &PlankHeap,
     deck_length: usize,
-    previous_line_junctions: HashSet<Junction>,
+    is_forbidden: &dyn Fn(&Junction) -> bool,
 ) -> Result<CalepineStep, CalepinageError> {
+    let step = select_planks_greedily(&the_plank_heap.planks, deck_length, is_forbidden);
+    if step.selected.total_length == deck_length {
+        return Ok(step);
+    }
+
+    match search_exact_fill(&the_plank_heap.planks, deck_length, is_forbidden, &|_| true) {
+        Some(step) => Ok(step),
+        None => assert_length_goal_fulfilled(step, deck_length),
+    }
+}
+
+fn select_planks_greedily(
+    planks: &[Plank],
+    deck_length: usize,
+    is_forbidden: &dyn Fn(&Junction) -> bool,
+) -> CalepineStep {
     let select_planks_fitting_length_goal = |step: CalepineStep, plank: &Plank| -> CalepineStep {
         let new_length = step.selected.total_length + plank.length;
         let junction = Junction(new_length);
 
         if new_length > deck_length {
-            let remaining = step.remaining.add(1, plank.length);
+            let remaining = step.remaining.add_plank(plank.clone());
             CalepineStep { remaining, ..step }
-        } else if previous_line_junctions.contains(&junction) {
-            let stash = Some(plank.clone());
+        } else if new_length < deck_length && is_forbidden(&junction) {
+            let stash = step.stash.add_plank(plank.clone());
             CalepineStep { stash, ..step }
         } else {
-            let selected = step.selected.add(1, plank.length);
+            let selected = step.selected.add_plank(plank.clone());
             CalepineStep { selected, ..step }
         }
     };
 
-    let mut step = the_plank_heap
-        .planks
+    let step = planks
         .iter()
         .fold(CalepineStep::default(), select_planks_fitting_length_goal);
 
-    step = match step.stash {
-        Some(plank) => select_planks_fitting_length_goal(CalepineStep { stash: None, ..step }, &plank),
-        None => step,
+    // stashed planks may fit once the line went further
+    let stashed = step.stash.planks.clone();
+    let step = stashed.iter().fold(
+        CalepineStep {
+            stash: PlankHeap::default(),
+            ..step
+        },
+        select_planks_fitting_length_goal,
+    );
+
+    let CalepineStep {
+        remaining, selected, stash,
+    } = step;
+    let mut remaining = PlankHeap::from_planks([remaining.planks, stash.planks].concat());
+    remaining.planks.sort_by_key(|plank| Reverse(plank.length));
+    CalepineStep {
+        remaining,
+        selected,
+        stash: PlankHeap::default(),
+    }
+}
+
+// Distinct lengths with how many times each comes, in order of first appearance.
+pub(crate) fn count_lengths(lengths: impl IntoIterator<Item = usize>) -> Vec<(usize, usize)> {
+    let mut counts: Vec<(usize, usize)> = vec![];
+    for length in lengths {
+        match counts.iter_mut().find(|(known, _)| *known == length) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((length, 1)),
+        }
+    }
+    counts
+}
+
+pub(crate) fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// Upper bound of combinations tried by the search, so big heaps stay fast.
+const SEARCH_BUDGET: usize = 1_000;
+
+// Depth first search of the planks filling exactly the line without forbidden junction,
+// with a sequence of lengths `accepts` takes, trying each distinct length once per position,
+// the longest first.
+fn search_exact_fill(
+    planks: &[Plank],
+    deck_length: usize,
+    is_forbidden: &dyn Fn(&Junction) -> bool,
+    accepts: &dyn Fn(&[usize]) -> bool,
+) -> Option<CalepineStep> {
+    let mut lengths = count_lengths(planks.iter().map(|plank| plank.length).filter(|&length| length > 0));
+    lengths.sort_by_key(|(length, _)| Reverse(*length));
+
+    let mut search = FillSearch {
+        lengths,
+        path: vec![],
+        dead_ends: HashSet::new(),
+        rejected: 0,
+        budget: SEARCH_BUDGET,
+        deck_length,
+        is_forbidden,
+        accepts,
     };
+    if !search.fill_from(0) {
+        return None;
+    }
+
+    let mut remaining = planks.to_vec();
+    let mut selected = PlankHeap::default();
+    for length in search.path {
+        let index = remaining.iter().position(|plank| plank.length == length)?;
+        selected = selected.add_plank(remaining.remove(index));
+    }
+    Some(CalepineStep {
+        remaining: PlankHeap::from_planks(remaining),
+        selected,
+        stash: PlankHeap::default(),
+    })
+}
+
+struct FillSearch<'a> {
+    // distinct lengths with their count still available
+    lengths: Vec<(usize, usize)>,
+    path: Vec<usize>,
+    // positions, with the counts left, the line was already found not to be completed from
+    dead_ends: HashSet<(usize, Vec<usize>)>,
+    // lines filled but not accepted, which make a dead end depend on the planks before it
+    rejected: usize,
+    budget: usize,
+    deck_length: usize,
+    is_forbidden: &'a dyn Fn(&Junction) -> bool,
+    accepts: &'a dyn Fn(&[usize]) -> bool,
+}
 
-   assert_length_goal_fulfilled(step, deck_length)
+impl FillSearch<'_> {
+    fn fill_from(&mut self, position: usize) -> bool {
+        let available: usize = self.lengths.iter().map(|(length, count)| length * count).sum();
+        if available < self.deck_length - position {
+            return false;
+        }
+        let dead_end = (position, self.lengths.iter().map(|(_, count)| *count).collect());
+        if self.dead_ends.contains(&dead_end) {
+            return false;
+        }
+        let rejected = self.rejected;
+        for index in 0..self.lengths.len() {
+            if self.budget == 0 {
+                return false;
+            }
+            self.budget -= 1;
+            let (length, count) = self.lengths[index];
+            let new_position = position + length;
+            if count == 0
+                || new_position > self.deck_length
+                || (new_position < self.deck_length && (self.is_forbidden)(&Junction(new_position)))
+            {
+                continue;
+            }
+            self.path.push(length);
+            self.lengths[index].1 -= 1;
+            if new_position == self.deck_length {
+                if (self.accepts)(&self.path) {
+                    return true;
+                }
+                self.rejected += 1;
+            } else if self.fill_from(new_position) {
+                return true;
+            }
+            self.lengths[index].1 += 1;
+            self.path.pop();
+        }
+        if self.rejected == rejected {
+            self.dead_ends.insert(dead_end);
+        }
+        false
+    }
+}
+
+// Distinct selections starting the line with each distinct length of the sorted heap in turn,
+// or the exact fill search when none of them fills the line.
+pub(crate) fn select_alternative_planks_for_line(
+    the_plank_heap: &PlankHeap,
+    deck_length: usize,
+    is_forbidden: &dyn Fn(&Junction) -> bool,
+) -> Result<Vec<CalepineStep>, CalepinageError> {
+    let planks = &the_plank_heap.planks;
+    let mut steps: Vec<CalepineStep> = vec![];
+    for start in 0..planks.len() {
+        if start > 0 && planks[start].length == planks[start - 1].length {
+            continue;
+        }
+        let mut rotated = planks.clone();
+        rotated.rotate_left(start);
+        let step = select_planks_greedily(&rotated, deck_length, is_forbidden);
+        if step.selected.total_length == deck_length
+            && steps.iter().all(|other| {
+                let lengths = |heap: &PlankHeap| heap.planks.iter().map(|plank| plank.length).collect::<Vec<usize>>();
+                lengths(&other.selected) != lengths(&step.selected)
+            })
+        {
+            steps.push(step);
+        }
+    }
+    if steps.is_empty() {
+        select_planks_for_line(the_plank_heap, deck_length, is_forbidden).map(|step| vec![step])
+    } else {
+        Ok(steps)
+    }
+}
+
+// First selection starting with a distinct length that does not copy a recent line,
+// or the exact fill search skipping the copies when each of them does.
+fn select_planks_for_unrepeated_line(
+    the_plank_heap: &PlankHeap,
+    deck_length: usize,
+    is_forbidden: &dyn Fn(&Junction) -> bool,
+    repetition: &RepetitionRule,
+    calepinage: &Calepinage,
+) -> Result<CalepineStep, CalepinageError> {
+    // only the lengths of the planks tell whether a line repeats an other
+    let unrepeated = |lengths: &[usize]| {
+        let plank_of = |&length: &usize| the_plank_heap.planks.iter().find(|plank| plank.length == length).cloned();
+        !repetition.repeats(&Line(lengths.iter().filter_map(plank_of).collect()), calepinage)
+    };
+    select_alternative_planks_for_line(the_plank_heap, deck_length, is_forbidden)?
+        .into_iter()
+        .find(|step| !repetition.repeats(&Line(step.selected.planks.clone()), calepinage))
+        .or_else(|| search_exact_fill(&the_plank_heap.planks, deck_length, is_forbidden, &unrepeated))
+        .ok_or(CalepinageError::RepeatedLine(calepinage.0.len()))
 }
 
 fn assert_length_goal_fulfilled(
@@ -297,17 +668,17 @@ fn foo() {
     };
     let plank_heap = PlankHeap::from_planks(
         vec![
-            Plank { length: 8 },
-            Plank { length: 5 },
-            Plank { length: 8 },
-            Plank { length: 5 },
-            Plank { length: 8 },
-            Plank { length: 5 },
+            Plank::new(8).unwrap(),
+            Plank::new(5).unwrap(),
+            Plank::new(8).unwrap(),
+            Plank::new(5).unwrap(),
+            Plank::new(8).unwrap(),
+            Plank::new(5).unwrap(),
         ], //
     );
     let result = calepine(plank_heap, deck);
     assert_that!(result).is_equal_to(
-        Err(CalepinageError::OnlyUnusablePlanksRemaining("remaining = [8, 8, 5, 5, 5], selected = [8], stash = None".to_string())))
+        Err(CalepinageError::OnlyUnusablePlanksRemaining("remaining = [8, 8, 5], selected = [8], stash = []".to_string())))
 }
 
 
@@ -317,17 +688,17 @@ fn step_to_string() {
     let step = CalepineStep {
         remaining: PlankHeap::from_planks(
             vec![
-                Plank { length: 8 },
-                Plank { length: 8 },
-                Plank { length: 5 },
-                Plank { length: 5 },
-                Plank { length: 5 },
+                Plank::new(8).unwrap(),
+                Plank::new(8).unwrap(),
+                Plank::new(5).unwrap(),
+                Plank::new(5).unwrap(),
+                Plank::new(5).unwrap(),
             ]),
         selected: PlankHeap::from_planks(
-            vec![Plank { length: 8 }]),
-        stash: None,
+            vec![Plank::new(8).unwrap()]),
+        stash: PlankHeap::default(),
     };
-    assert_that!(step.to_string()).is_equal_to("remaining = [8, 8, 5, 5, 5], selected = [8], stash = None".to_string());
+    assert_that!(step.to_string()).is_equal_to("remaining = [8, 8, 5, 5, 5], selected = [8], stash = []".to_string());
 }
 
 
@@ -339,23 +710,76 @@ fn make_stash_algo_fail() {
     };
     let plank_heap = PlankHeap::from_planks(
         vec![
-            Plank { length: 10 },
-            Plank { length: 10 },
-            Plank { length: 10 },
-            Plank { length: 2 },
-            Plank { length: 2 },
-            Plank { length: 2 },
+            Plank::new(10).unwrap(),
+            Plank::new(10).unwrap(),
+            Plank::new(10).unwrap(),
+            Plank::new(2).unwrap(),
+            Plank::new(2).unwrap(),
+            Plank::new(2).unwrap(),
         ], //
     );
     let result = calepine(plank_heap, deck);
 
     assert_that!(result).is_equal_to(Ok(
         Calepinage::default()
-            .with_line(plank_line![Plank { length: 10 }, Plank { length: 2 }])
-            .with_line(plank_line![Plank { length: 2 }, Plank { length: 10}])
-            .with_line(plank_line![Plank { length: 10 }, Plank { length: 2 }])
+            .with_line(plank_line![Plank::new(10).unwrap(), Plank::new(2).unwrap()])
+            .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(10).unwrap()])
+            .with_line(plank_line![Plank::new(10).unwrap(), Plank::new(2).unwrap()])
     ));
 }
 
+#[test]
+fn should_search_exact_fill_when_stashed_planks_do_not_fit() {
+    let plank_heap = PlankHeap::default().add(2, 4).add(2, 3).add(2, 2);
+    let is_forbidden = |junction: &Junction| *junction == Junction(4) || *junction == Junction(6);
+
+    let step = select_planks_for_line(&plank_heap, 8, &is_forbidden).unwrap();
+
+    assert_that!(step.selected.to_string()).is_equal_to("3, 2, 3".to_string());
+    assert_that!(step.remaining.to_string()).is_equal_to("4, 4, 2".to_string());
+}
+
 // "remaining = [8, 8, 5, 5, 5], selected = [8], stash = None
 // "remaining = [5, 5, 5], selected = [8, 8, 8], stash = None
+
+#[test]
+fn should_fill_a_line_when_an_other_branch_failed_at_the_same_position() {
+    let cases: [(usize, [usize; 6]); 3] = [
+        (16, [8, 5, 4, 5, 1, 1]),
+        (21, [3, 7, 7, 8, 2, 2]),
+        (15, [9, 9, 3, 4, 1, 7]),
+    ];
+    for (deck_length, lengths) in cases.iter() {
+        let deck = Deck::new(*deck_length, 1).unwrap();
+        let plank_heap = PlankHeap::from_planks(lengths.iter().map(|&length| Plank::new(length).unwrap()).collect());
+
+        let calepinage = calepine(plank_heap, deck).unwrap();
+
+        assert_eq!(*deck_length, calepinage.0[0].lengths().iter().sum::<usize>());
+    }
+}
+
+#[test]
+fn greedy_selection_should_give_back_stashed_planks() {
+    let is_forbidden = |junction: &Junction| *junction == Junction(3);
+
+    let step = select_planks_greedily(&[Plank::new(3).unwrap()], 5, &is_forbidden);
+
+    assert_eq!("3", step.remaining.to_string());
+    assert_eq!(
+        Err(CalepinageError::OnlyUnusablePlanksRemaining("remaining = [3], selected = [], stash = []".to_string())),
+        select_planks_for_line(&PlankHeap::default().add(1, 3), 5, &is_forbidden)
+    );
+}
+
+#[test]
+fn should_search_unrepeated_fill_when_every_selection_repeats() {
+    let plank_heap = PlankHeap::default().add(2, 2).add(2, 1);
+    let calepinage = Calepinage::default().with_line(plank_line![Plank::new(2).unwrap(), Plank::new(2).unwrap()]);
+    let is_forbidden = |junction: &Junction| *junction == Junction(1);
+    let repetition = RepetitionRule::new(1).unwrap();
+
+    let step = select_planks_for_unrepeated_line(&plank_heap, 4, &is_forbidden, &repetition, &calepinage).unwrap();
+
+    assert_that!(step.selected.to_string()).is_equal_to("2, 1, 1".to_string());
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::calepinage::{Calepinage, PlankHeap};
+use crate::score::Weights;
+
+/// Stops a solver running on another thread: clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Where a solver is: lines of its best layout, and the score of that layout (the lower the better).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    pub lines_completed: usize,
+    pub best_score: Option<f64>,
+}
+
+type ProgressCallback<'a> = Box<dyn FnMut(&Progress) + 'a>;
+
+/// Deadline, cancellation and progress reporting of a solver. Layouts built line by line
+/// are scored with `weights` in reports.
+#[derive(Default)]
+pub struct Control<'a> {
+    deadline: Option<Instant>,
+    cancellation: Option<CancellationToken>,
+    on_progress: Option<ProgressCallback<'a>>,
+    weights: Weights,
+}
+
+impl<'a> Control<'a> {
+    pub fn with_deadline(self, deadline: Instant) -> Self {
+        Control {
+            deadline: Some(deadline),
+            ..self
+        }
+    }
+
+    pub fn with_budget(self, budget: Duration) -> Self {
+        self.with_deadline(Instant::now() + budget)
+    }
+
+    pub fn with_cancellation(self, cancellation: CancellationToken) -> Self {
+        Control {
+            cancellation: Some(cancellation),
+            ..self
+        }
+    }
+
+    pub fn with_progress(self, on_progress: impl FnMut(&Progress) + 'a) -> Self {
+        Control {
+            on_progress: Some(Box::new(on_progress)),
+            ..self
+        }
+    }
+
+    pub fn with_weights(self, weights: Weights) -> Self {
+        Control { weights, ..self }
+    }
+
+    // Same deadline and cancellation, without reporting: for a solver run by another one.
+    pub(crate) fn limits(&self) -> Control<'static> {
+        Control {
+            deadline: self.deadline,
+            cancellation: self.cancellation.clone(),
+            on_progress: None,
+            weights: self.weights.clone(),
+        }
+    }
+
+    pub(crate) fn weights(&self) -> &Weights {
+        &self.weights
+    }
+
+    /// True once the deadline is passed or the solver is cancelled.
+    pub fn is_interrupted(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            || self
+                .cancellation
+                .as_ref()
+                .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    pub(crate) fn report(&mut self, progress: Progress) {
+        if let Some(on_progress) = self.on_progress.as_mut() {
+            on_progress(&progress);
+        }
+    }
+
+    pub(crate) fn report_layout(&mut self, calepinage: &Calepinage, plank_heap: &PlankHeap) {
+        if self.on_progress.is_some() {
+            let best_score = Some(self.weights.evaluate(calepinage, plank_heap));
+            self.report(Progress {
+                lines_completed: calepinage.0.len(),
+                best_score,
+            });
+        }
+    }
+}
+
+impl fmt::Debug for Control<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Control")
+            .field("deadline", &self.deadline)
+            .field("cancellation", &self.cancellation)
+            .field("weights", &self.weights)
+            .finish()
+    }
+}
+
+/// Layout returned by a controlled solver. When `interrupted`, it is the best layout found
+/// so far, which follows the rules but may have fewer lines than the deck.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub calepinage: Calepinage,
+    pub interrupted: bool,
+}
+
+#[test]
+fn cancelling_a_clone_should_interrupt() {
+    let token = CancellationToken::new();
+    let control = Control::default().with_cancellation(token.clone());
+
+    assert!(!control.is_interrupted());
+    token.cancel();
+    assert!(control.is_interrupted());
+}
+
+#[test]
+fn passed_deadline_should_interrupt() {
+    let control = Control::default().with_budget(Duration::from_secs(0));
+
+    assert!(control.is_interrupted());
+}
+
+#[cfg(test)]
+use crate::calepinage::{calepine_with_control, Deck};
+#[cfg(test)]
+use crate::rules::Rules;
+
+#[test]
+fn progress_should_be_reported_after_each_line() {
+    let deck = Deck::new(10, 3).unwrap();
+    let plank_heap = PlankHeap::default().add(4, 3).add(4, 4).add(4, 5);
+    let mut lines_completed = vec![];
+
+    let outcome = {
+        let mut control = Control::default().with_progress(|progress: &Progress| lines_completed.push(progress.lines_completed));
+        calepine_with_control(plank_heap, deck, &Rules::default(), &mut control).unwrap()
+    };
+
+    assert!(!outcome.interrupted);
+    assert_eq!(vec![1, 2, 3], lines_completed);
+}
+
+#[test]
+fn cancelled_solver_should_return_layout_so_far() {
+    let deck = Deck::new(10, 3).unwrap();
+    let plank_heap = PlankHeap::default().add(4, 3).add(4, 4).add(4, 5);
+    let token = CancellationToken::new();
+    let cancelling = token.clone();
+    let mut control = Control::default()
+        .with_cancellation(token)
+        .with_progress(move |progress: &Progress| {
+            if progress.lines_completed == 2 {
+                cancelling.cancel();
+            }
+        });
+
+    let outcome = calepine_with_control(plank_heap, deck, &Rules::default(), &mut control).unwrap();
+
+    assert!(outcome.interrupted);
+    assert_eq!(2, outcome.calepinage.0.len());
+    assert!(Rules::default().accept(&outcome.calepinage));
+}
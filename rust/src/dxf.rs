@@ -0,0 +1,136 @@
+use crate::calepinage::{Calepinage, Deck};
+use crate::geometry::Geometry;
+
+pub const OUTLINE_LAYER: &str = "OUTLINE";
+pub const PLANKS_LAYER: &str = "PLANKS";
+pub const JUNCTIONS_LAYER: &str = "JUNCTIONS";
+pub const JOISTS_LAYER: &str = "JOISTS";
+pub const ANNOTATIONS_LAYER: &str = "ANNOTATIONS";
+
+// layer and its colour number
+const LAYERS: [(&str, u8); 5] = [
+    (OUTLINE_LAYER, 7),
+    (PLANKS_LAYER, 3),
+    (JUNCTIONS_LAYER, 1),
+    (JOISTS_LAYER, 5),
+    (ANNOTATIONS_LAYER, 2),
+];
+
+/// The calepinage as an ASCII DXF (R12) drawing in millimetres, a unit R12 has no header
+/// variable for, the first line along y = 0:
+/// the deck outline, a closed polyline per plank, a line per junction, a line per joist
+/// every `joist_spacing` from x = 0 if given, and the name of each plank.
+pub fn to_dxf(deck: &Deck, calepinage: &Calepinage, plank_width: usize, joist_spacing: Option<usize>) -> String {
+    let geometry = Geometry::new(calepinage, plank_width);
+    let deck_width = deck.width * plank_width;
+    let mut dxf = Dxf::default();
+
+    dxf.pair(0, "SECTION").pair(2, "HEADER");
+    dxf.pair(9, "$ACADVER").pair(1, "AC1009");
+    dxf.pair(0, "ENDSEC");
+
+    dxf.pair(0, "SECTION").pair(2, "TABLES");
+    dxf.pair(0, "TABLE").pair(2, "LAYER").pair(70, LAYERS.len());
+    for (name, colour) in LAYERS.iter() {
+        dxf.pair(0, "LAYER").pair(2, name).pair(70, 0).pair(62, colour).pair(6, "CONTINUOUS");
+    }
+    dxf.pair(0, "ENDTAB");
+    dxf.pair(0, "ENDSEC");
+
+    dxf.pair(0, "SECTION").pair(2, "ENTITIES");
+    dxf.rectangle(OUTLINE_LAYER, 0, 0, deck.length, deck_width);
+    for plank in &geometry.planks {
+        dxf.rectangle(PLANKS_LAYER, plank.x_start, plank.y, plank.x_end, plank.y + plank.width);
+        let name = plank.plank.id.clone().unwrap_or_else(|| format!("#{}", plank.index + 1));
+        dxf.text(
+            ANNOTATIONS_LAYER,
+            plank.x_start + plank_width / 4,
+            plank.y + plank_width / 3,
+            plank_width / 3,
+            &format!("{} {}", name, plank.plank.length),
+        );
+    }
+    for (line_index, line) in calepinage.0.iter().enumerate() {
+        for junction in line.compute_junction() {
+            let y = line_index * plank_width;
+            dxf.line(JUNCTIONS_LAYER, junction.position(), y, junction.position(), y + plank_width);
+        }
+    }
+    if let Some(spacing) = joist_spacing.filter(|&spacing| spacing > 0) {
+        for x in (0..=deck.length).step_by(spacing) {
+            dxf.line(JOISTS_LAYER, x, 0, x, deck_width);
+        }
+    }
+    dxf.pair(0, "ENDSEC");
+    dxf.pair(0, "EOF");
+    dxf.0
+}
+
+// Group codes and values, one per line.
+#[derive(Default)]
+struct Dxf(String);
+
+impl Dxf {
+    fn pair(&mut self, code: u16, value: impl std::fmt::Display) -> &mut Self {
+        self.0.push_str(&format!("{:>3}\n{}\n", code, value));
+        self
+    }
+
+    fn point(&mut self, x: usize, y: usize) -> &mut Self {
+        self.pair(10, x).pair(20, y).pair(30, 0)
+    }
+
+    fn line(&mut self, layer: &str, x1: usize, y1: usize, x2: usize, y2: usize) {
+        self.pair(0, "LINE").pair(8, layer).point(x1, y1).pair(11, x2).pair(21, y2).pair(31, 0);
+    }
+
+    fn rectangle(&mut self, layer: &str, x1: usize, y1: usize, x2: usize, y2: usize) {
+        self.pair(0, "POLYLINE").pair(8, layer).pair(66, 1).point(0, 0).pair(70, 1);
+        for (x, y) in [(x1, y1), (x2, y1), (x2, y2), (x1, y2)].iter() {
+            self.pair(0, "VERTEX").pair(8, layer).point(*x, *y);
+        }
+        self.pair(0, "SEQEND").pair(8, layer);
+    }
+
+    // A value is one line of the file: line breaks and other control characters become spaces.
+    fn text(&mut self, layer: &str, x: usize, y: usize, height: usize, value: &str) {
+        let value: String = value.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+        self.pair(0, "TEXT").pair(8, layer).point(x, y).pair(40, height).pair(1, value);
+    }
+}
+
+#[cfg(test)]
+use crate::calepinage::{Line, Plank};
+#[cfg(test)]
+use crate::plank_line;
+
+#[test]
+fn dxf_should_draw_each_kind_of_entity_on_its_layer() {
+    let deck = Deck::new(3000, 2).unwrap();
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(2000).unwrap().with_id("B-0412"), Plank::new(1000).unwrap()])
+        .with_line(plank_line![Plank::new(1000).unwrap(), Plank::new(2000).unwrap()]);
+
+    let dxf = to_dxf(&deck, &calepinage, 140, Some(500));
+
+    let entities_on = |layer: &str| dxf.matches(&format!("  8\n{}\n", layer)).count();
+    // a polyline is its header, 4 vertices and its end
+    assert_eq!(6, entities_on(OUTLINE_LAYER));
+    assert_eq!(4 * 6, entities_on(PLANKS_LAYER));
+    assert_eq!(2, entities_on(JUNCTIONS_LAYER));
+    assert_eq!(7, entities_on(JOISTS_LAYER));
+    assert_eq!(4, entities_on(ANNOTATIONS_LAYER));
+    assert!(dxf.contains("  1\nB-0412 2000\n"));
+    assert!(dxf.ends_with("  0\nEOF\n"));
+    assert!(!dxf.contains("$INSUNITS"));
+}
+
+#[test]
+fn dxf_text_should_hold_on_one_line() {
+    let deck = Deck::new(1000, 1).unwrap();
+    let calepinage = Calepinage::default().with_line(plank_line![Plank::new(1000).unwrap().with_id("B-04\r\n12")]);
+
+    let dxf = to_dxf(&deck, &calepinage, 140, None);
+
+    assert!(dxf.contains("  1\nB-04  12 1000\n"));
+}
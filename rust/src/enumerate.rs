@@ -0,0 +1,229 @@
+use crate::calepinage::{count_lengths, Calepinage, Deck, Junction, Line, Plank, PlankHeap};
+use crate::control::{Control, Progress};
+use crate::rules::Rules;
+
+/// Every layout following the rules, yielded lazily line after line. Planks of the same length
+/// are interchangeable: two layouts are distinct when their lengths differ somewhere.
+pub struct Layouts<'a> {
+    control: Control<'a>,
+    deck_length: usize,
+    width: usize,
+    rules: Rules,
+    limit: Option<usize>,
+    yielded: usize,
+    // one frame per line being tried: the lines possible after the layout above it
+    stack: Vec<Frame>,
+}
+
+struct Frame {
+    calepinage: Calepinage,
+    // distinct lengths with the number of planks left of each
+    counts: Vec<(usize, usize)>,
+    lines: Vec<Vec<usize>>,
+    next: usize,
+}
+
+/// Layouts of `plank_heap` on `deck` following `rules`, stopping after `limit` of them if any.
+pub fn enumerate_layouts(plank_heap: &PlankHeap, deck: &Deck, rules: &Rules, limit: Option<usize>) -> Layouts<'static> {
+    enumerate_layouts_with_control(plank_heap, deck, rules, limit, Control::default())
+}
+
+/// Like `enumerate_layouts`, stopping as well once `control` is interrupted, which
+/// `Layouts::is_interrupted` tells, and reporting progress with each layout yielded.
+pub fn enumerate_layouts_with_control<'a>(
+    plank_heap: &PlankHeap,
+    deck: &Deck,
+    rules: &Rules,
+    limit: Option<usize>,
+    control: Control<'a>,
+) -> Layouts<'a> {
+    let counts = counts_of(plank_heap);
+    let calepinage = Calepinage::default();
+    let lines = possible_lines(&counts, deck.length, rules, &calepinage);
+    Layouts {
+        control,
+        deck_length: deck.length,
+        width: deck.width,
+        rules: rules.clone(),
+        limit,
+        yielded: 0,
+        stack: vec![Frame {
+            calepinage,
+            counts,
+            lines,
+            next: 0,
+        }],
+    }
+}
+
+/// Number of layouts `enumerate_layouts` would yield without a limit, without keeping them.
+pub fn count_layouts(plank_heap: &PlankHeap, deck: &Deck, rules: &Rules) -> usize {
+    count_from(&Calepinage::default(), &counts_of(plank_heap), deck, rules)
+}
+
+fn count_from(calepinage: &Calepinage, counts: &[(usize, usize)], deck: &Deck, rules: &Rules) -> usize {
+    let lines = possible_lines(counts, deck.length, rules, calepinage);
+    if calepinage.0.len() + 1 == deck.width {
+        return lines.len();
+    }
+    lines
+        .iter()
+        .map(|lengths| {
+            count_from(
+                &calepinage.clone().with_line(line_of(lengths)),
+                &without(counts, lengths),
+                deck,
+                rules,
+            )
+        })
+        .sum()
+}
+
+impl Layouts<'_> {
+    /// True when the control stopped the enumeration before every layout was yielded.
+    pub fn is_interrupted(&self) -> bool {
+        !self.stack.is_empty() && self.control.is_interrupted()
+    }
+}
+
+impl Iterator for Layouts<'_> {
+    type Item = Calepinage;
+
+    fn next(&mut self) -> Option<Calepinage> {
+        if self.width == 0 || self.limit.is_some_and(|limit| self.yielded >= limit) {
+            return None;
+        }
+        while let Some(frame) = self.stack.last_mut() {
+            if self.control.is_interrupted() {
+                return None;
+            }
+            if frame.next == frame.lines.len() {
+                self.stack.pop();
+                continue;
+            }
+            let lengths = frame.lines[frame.next].clone();
+            frame.next += 1;
+            let calepinage = frame.calepinage.clone().with_line(line_of(&lengths));
+            if calepinage.0.len() == self.width {
+                self.yielded += 1;
+                self.control.report(Progress {
+                    lines_completed: self.width,
+                    best_score: None,
+                });
+                return Some(calepinage);
+            }
+            let counts = without(&frame.counts, &lengths);
+            let lines = possible_lines(&counts, self.deck_length, &self.rules, &calepinage);
+            self.stack.push(Frame {
+                calepinage,
+                counts,
+                lines,
+                next: 0,
+            });
+        }
+        None
+    }
+}
+
+fn counts_of(plank_heap: &PlankHeap) -> Vec<(usize, usize)> {
+    let mut counts = count_lengths(plank_heap.planks().iter().map(|plank| plank.length).filter(|&length| length > 0));
+    counts.sort_unstable_by(|lhs, rhs| rhs.cmp(lhs));
+    counts
+}
+
+fn without(counts: &[(usize, usize)], lengths: &[usize]) -> Vec<(usize, usize)> {
+    counts
+        .iter()
+        .map(|&(length, count)| (length, count - lengths.iter().filter(|&&used| used == length).count()))
+        .collect()
+}
+
+fn line_of(lengths: &[usize]) -> Line {
+    Line(lengths.iter().map(|&length| Plank::new(length).unwrap()).collect())
+}
+
+// Sequences of lengths filling a line after the calepinage, the rules allowing.
+fn possible_lines(counts: &[(usize, usize)], deck_length: usize, rules: &Rules, calepinage: &Calepinage) -> Vec<Vec<usize>> {
+    let is_forbidden = rules.forbidden_junctions(calepinage);
+    let mut lines = vec![];
+    let mut counts = counts.to_vec();
+    fill(&mut counts, deck_length, &is_forbidden, &mut vec![], 0, &mut lines);
+    lines.retain(|lengths| {
+        rules
+            .repetition
+            .as_ref()
+            .is_none_or(|repetition| !repetition.repeats(&line_of(lengths), calepinage))
+    });
+    lines
+}
+
+fn fill(
+    counts: &mut Vec<(usize, usize)>,
+    deck_length: usize,
+    is_forbidden: &dyn Fn(&Junction) -> bool,
+    path: &mut Vec<usize>,
+    position: usize,
+    lines: &mut Vec<Vec<usize>>,
+) {
+    if position == deck_length {
+        lines.push(path.clone());
+        return;
+    }
+    if position > 0 && is_forbidden(&Junction(position)) {
+        return;
+    }
+    for index in 0..counts.len() {
+        let (length, count) = counts[index];
+        if count == 0 || position + length > deck_length {
+            continue;
+        }
+        counts[index].1 -= 1;
+        path.push(length);
+        fill(counts, deck_length, is_forbidden, path, position + length, lines);
+        path.pop();
+        counts[index].1 += 1;
+    }
+}
+
+#[test]
+fn should_enumerate_layouts_modulo_identical_lengths() {
+    let deck = Deck::new(3, 2).unwrap();
+    let plank_heap = PlankHeap::default().add(2, 2).add(2, 1);
+
+    let layouts: Vec<Vec<Vec<usize>>> = enumerate_layouts(&plank_heap, &deck, &Rules::default(), None)
+        .map(|Calepinage(lines)| lines.iter().map(Line::lengths).collect())
+        .collect();
+
+    assert_eq!(vec![vec![vec![2, 1], vec![1, 2]], vec![vec![1, 2], vec![2, 1]]], layouts);
+    assert_eq!(2, count_layouts(&plank_heap, &deck, &Rules::default()));
+}
+
+#[test]
+fn should_stop_at_limit() {
+    let deck = Deck::new(5, 2).unwrap();
+    let plank_heap = PlankHeap::default().add(2, 3).add(2, 2).add(2, 1);
+    let rules = Rules::default();
+
+    assert_eq!(3, enumerate_layouts(&plank_heap, &deck, &rules, Some(3)).count());
+    assert_eq!(10, count_layouts(&plank_heap, &deck, &rules));
+    assert_eq!(10, enumerate_layouts(&plank_heap, &deck, &rules, None).count());
+}
+
+#[test]
+fn should_stop_when_cancelled() {
+    use crate::control::CancellationToken;
+
+    let deck = Deck::new(5, 2).unwrap();
+    let plank_heap = PlankHeap::default().add(2, 3).add(2, 2).add(2, 1);
+    let token = CancellationToken::new();
+    let cancelling = token.clone();
+    let control = Control::default()
+        .with_cancellation(token)
+        .with_progress(move |_: &Progress| cancelling.cancel());
+
+    let mut layouts = enumerate_layouts_with_control(&plank_heap, &deck, &Rules::default(), None, control);
+
+    assert!(layouts.next().is_some());
+    assert!(layouts.next().is_none());
+    assert!(layouts.is_interrupted());
+}
@@ -0,0 +1,110 @@
+use crate::calepinage::{Calepinage, Plank};
+
+/// A piece of a calepinage where it lies on the deck, in millimetres: lines run along x
+/// from 0, line `line` covering y from `y` to `y + width`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacedPlank {
+    /// Rank of the plank in the geometry, its own id being `plank.id`.
+    pub index: usize,
+    pub line: usize,
+    pub x_start: usize,
+    pub x_end: usize,
+    pub y: usize,
+    pub width: usize,
+    pub plank: Plank,
+}
+
+impl PlacedPlank {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x_start..self.x_end).contains(&x) && (self.y..self.y + self.width).contains(&y)
+    }
+
+    /// True when x falls inside the plank rather than on one of its ends.
+    pub fn crosses(&self, x: usize) -> bool {
+        self.x_start < x && x < self.x_end
+    }
+}
+
+/// Planks of a calepinage with absolute coordinates, numbered line after line from 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Geometry {
+    pub planks: Vec<PlacedPlank>,
+}
+
+impl Geometry {
+    /// Lays the lines of `calepinage` side by side, each `plank_width` wide.
+    pub fn new(calepinage: &Calepinage, plank_width: usize) -> Self {
+        let Calepinage(lines) = calepinage;
+        let mut planks = vec![];
+        for (line_index, line) in lines.iter().enumerate() {
+            let mut x = 0;
+            for plank in &line.0 {
+                planks.push(PlacedPlank {
+                    index: planks.len(),
+                    line: line_index,
+                    x_start: x,
+                    x_end: x + plank.length,
+                    y: line_index * plank_width,
+                    width: plank_width,
+                    plank: plank.clone(),
+                });
+                x += plank.length;
+            }
+        }
+        Geometry { planks }
+    }
+
+    pub fn plank_at(&self, x: usize, y: usize) -> Option<&PlacedPlank> {
+        self.planks.iter().find(|plank| plank.contains(x, y))
+    }
+
+    /// Planks a joist at `x` runs under without supporting one of their ends.
+    pub fn crossing(&self, x: usize) -> Vec<&PlacedPlank> {
+        self.planks.iter().filter(|plank| plank.crosses(x)).collect()
+    }
+
+    pub fn line(&self, line: usize) -> impl Iterator<Item = &PlacedPlank> {
+        self.planks.iter().filter(move |plank| plank.line == line)
+    }
+}
+
+#[cfg(test)]
+use crate::calepinage::Line;
+#[cfg(test)]
+use crate::plank_line;
+
+#[cfg(test)]
+fn two_lines() -> Calepinage {
+    Calepinage::default()
+        .with_line(plank_line![Plank::new(1200).unwrap(), Plank::new(800).unwrap()])
+        .with_line(plank_line![Plank::new(500).unwrap(), Plank::new(1500).unwrap()])
+}
+
+#[test]
+fn geometry_should_place_planks_line_after_line() {
+    let geometry = Geometry::new(&two_lines(), 140);
+
+    let placed = &geometry.planks[3];
+    assert_eq!((3, 1), (placed.index, placed.line));
+    assert_eq!((500, 2000, 140, 140), (placed.x_start, placed.x_end, placed.y, placed.width));
+}
+
+#[test]
+fn geometry_should_find_plank_at_point() {
+    let geometry = Geometry::new(&two_lines(), 140);
+
+    assert_eq!(Some(1), geometry.plank_at(1200, 139).map(|plank| plank.index));
+    assert_eq!(Some(2), geometry.plank_at(499, 140).map(|plank| plank.index));
+    assert_eq!(None, geometry.plank_at(2000, 0));
+    assert_eq!(None, geometry.plank_at(0, 280));
+}
+
+#[test]
+fn geometry_should_find_planks_crossing_joist() {
+    let geometry = Geometry::new(&two_lines(), 140);
+
+    let indices = |x: usize| geometry.crossing(x).iter().map(|plank| plank.index).collect::<Vec<usize>>();
+    assert_eq!(vec![0, 3], indices(600));
+    assert_eq!(vec![3], indices(1200));
+    assert_eq!(vec![1, 3], indices(1500));
+}
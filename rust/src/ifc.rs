@@ -0,0 +1,283 @@
+use crate::calepinage::{Calepinage, Deck};
+use crate::geometry::Geometry;
+use crate::obj::DeckModel;
+use crate::random::Random;
+
+const GUID_ALPHABET: &[u8; 64] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz_$";
+
+/// Minimal IFC4 model of the deck in millimetres: a site holding an `IfcCovering` of the deck
+/// made of an `IfcMember` per plank, each a box extruded `model.thickness` up,
+/// with its length, width and area as quantities and `material` as material.
+/// The covering has no body of its own, its planks being its shape.
+/// Identifiers derive from `seed`, the deck and the layout, so exporting the same layout twice
+/// gives the same file and other layouts get other identifiers.
+pub fn to_ifc(deck: &Deck, calepinage: &Calepinage, model: &DeckModel, material: &str, seed: u64) -> String {
+    let geometry = Geometry::new(calepinage, model.plank_width);
+    let pitch = model.plank_width + model.gap;
+    let deck_width = (deck.width * pitch).saturating_sub(model.gap);
+    let mut step = Step {
+        entities: vec![],
+        guids: Random::new(guid_seed(seed, deck, calepinage)),
+    };
+
+    let origin = step.add("IFCCARTESIANPOINT((0.,0.,0.))".to_string());
+    let world = step.add(format!("IFCAXIS2PLACEMENT3D(#{},$,$)", origin));
+    let context = step.add(format!("IFCGEOMETRICREPRESENTATIONCONTEXT($,'Model',3,1.E-05,#{},$)", world));
+    let length_unit = step.add("IFCSIUNIT(*,.LENGTHUNIT.,.MILLI.,.METRE.)".to_string());
+    let area_unit = step.add("IFCSIUNIT(*,.AREAUNIT.,$,.SQUARE_METRE.)".to_string());
+    let units = step.add(format!("IFCUNITASSIGNMENT((#{},#{}))", length_unit, area_unit));
+    let project_guid = step.guid();
+    let project = step.add(format!(
+        "IFCPROJECT('{}',$,'Deck',$,$,$,$,(#{}),#{})",
+        project_guid, context, units
+    ));
+    let site_placement = step.add(format!("IFCLOCALPLACEMENT($,#{})", world));
+    let site_guid = step.guid();
+    let site = step.add(format!(
+        "IFCSITE('{}',$,'Site',$,$,#{},$,$,.ELEMENT.,$,$,$,$,$)",
+        site_guid, site_placement
+    ));
+    let up = step.add("IFCDIRECTION((0.,0.,1.))".to_string());
+
+    let covering_placement = step.add(format!("IFCLOCALPLACEMENT(#{},#{})", site_placement, world));
+    let covering_guid = step.guid();
+    let covering = step.add(format!(
+        "IFCCOVERING('{}',$,'Deck',$,$,#{},$,$,.FLOORING.)",
+        covering_guid, covering_placement
+    ));
+    let covering_quantities = [
+        step.add(format!("IFCQUANTITYLENGTH('Length',$,$,{},$)", real(deck.length as f64))),
+        step.add(format!("IFCQUANTITYLENGTH('Width',$,$,{},$)", real(deck_width as f64))),
+        step.add(format!("IFCQUANTITYAREA('NetArea',$,$,{},$)", square_metres(deck.length, deck_width))),
+    ];
+    step.quantities(covering, "Qto_CoveringBaseQuantities", &covering_quantities);
+
+    let mut members = vec![];
+    for plank in &geometry.planks {
+        let corner = step.add(format!(
+            "IFCCARTESIANPOINT(({},{},0.))",
+            real(plank.x_start as f64),
+            real((plank.line * pitch) as f64)
+        ));
+        let axes = step.add(format!("IFCAXIS2PLACEMENT3D(#{},$,$)", corner));
+        let placement = step.add(format!("IFCLOCALPLACEMENT(#{},#{})", covering_placement, axes));
+        let length = plank.x_end - plank.x_start;
+        let shape = step.extruded_box(context, up, length, plank.width, model.thickness);
+        let name = plank.plank.id.clone().unwrap_or_else(|| format!("#{}", plank.index + 1));
+        let description = format!("line {}, length {} mm", plank.line + 1, length);
+        let member_guid = step.guid();
+        let member = step.add(format!(
+            "IFCMEMBER('{}',$,'{}','{}',$,#{},#{},$,.PLATE.)",
+            member_guid,
+            escape(&name),
+            escape(&description),
+            placement,
+            shape
+        ));
+        let quantities = [
+            step.add(format!("IFCQUANTITYLENGTH('Length',$,$,{},$)", real(length as f64))),
+            step.add(format!("IFCQUANTITYLENGTH('Width',$,$,{},$)", real(plank.width as f64))),
+            step.add(format!("IFCQUANTITYAREA('NetArea',$,$,{},$)", square_metres(length, plank.width))),
+        ];
+        step.quantities(member, "Qto_MemberBaseQuantities", &quantities);
+        members.push(member);
+    }
+
+    let material = step.add(format!("IFCMATERIAL('{}',$,$)", escape(material)));
+    let guid = step.guid();
+    step.add(format!(
+        "IFCRELASSOCIATESMATERIAL('{}',$,$,$,({}),#{})",
+        guid,
+        references(&[&[covering][..], &members].concat()),
+        material
+    ));
+    let guid = step.guid();
+    step.add(format!("IFCRELAGGREGATES('{}',$,$,$,#{},(#{}))", guid, project, site));
+    if !members.is_empty() {
+        let guid = step.guid();
+        step.add(format!("IFCRELAGGREGATES('{}',$,$,$,#{},({}))", guid, covering, references(&members)));
+    }
+    let guid = step.guid();
+    step.add(format!(
+        "IFCRELCONTAINEDINSPATIALSTRUCTURE('{}',$,$,$,(#{}),#{})",
+        guid, covering, site
+    ));
+
+    let mut ifc = String::new();
+    ifc.push_str("ISO-10303-21;\nHEADER;\n");
+    ifc.push_str("FILE_DESCRIPTION(('ViewDefinition [ReferenceView]'),'2;1');\n");
+    ifc.push_str("FILE_NAME('deck.ifc','',(''),(''),'calepinage','calepinage','');\n");
+    ifc.push_str("FILE_SCHEMA(('IFC4'));\nENDSEC;\nDATA;\n");
+    for (index, entity) in step.entities.iter().enumerate() {
+        ifc.push_str(&format!("#{}={};\n", index + 1, entity));
+    }
+    ifc.push_str("ENDSEC;\nEND-ISO-10303-21;\n");
+    ifc
+}
+
+// FNV-1a of the deck and the planks of each line, their ids included, from `seed`.
+fn guid_seed(seed: u64, deck: &Deck, calepinage: &Calepinage) -> u64 {
+    let mut layout = format!("{}x{}", deck.length, deck.width);
+    for line in calepinage.0.iter() {
+        layout.push('|');
+        for plank in line.0.iter() {
+            layout.push_str(&format!("{}:{};", plank.length, plank.id.as_deref().unwrap_or("")));
+        }
+    }
+    layout
+        .bytes()
+        .fold(seed ^ 0xCBF2_9CE4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3))
+}
+
+struct Step {
+    entities: Vec<String>,
+    guids: Random,
+}
+
+impl Step {
+    // Adds an entity and returns its number.
+    fn add(&mut self, entity: String) -> usize {
+        self.entities.push(entity);
+        self.entities.len()
+    }
+
+    // 128 bits in the 22 characters of IFC's base 64, the first one taking the 2 highest bits.
+    fn guid(&mut self) -> String {
+        let value = (self.guids.next_u64() as u128) << 64 | self.guids.next_u64() as u128;
+        (0..22)
+            .map(|index| {
+                let shift = 6 * (21 - index);
+                GUID_ALPHABET[((value >> shift) & 0x3f) as usize] as char
+            })
+            .collect()
+    }
+
+    // Shape of a box `length` along x, `width` along y and `height` up from its placement.
+    fn extruded_box(&mut self, context: usize, up: usize, length: usize, width: usize, height: usize) -> usize {
+        let centre = self.add(format!(
+            "IFCCARTESIANPOINT(({},{}))",
+            real(length as f64 / 2.0),
+            real(width as f64 / 2.0)
+        ));
+        let position = self.add(format!("IFCAXIS2PLACEMENT2D(#{},$)", centre));
+        let profile = self.add(format!(
+            "IFCRECTANGLEPROFILEDEF(.AREA.,$,#{},{},{})",
+            position,
+            real(length as f64),
+            real(width as f64)
+        ));
+        let origin = self.add("IFCCARTESIANPOINT((0.,0.,0.))".to_string());
+        let axes = self.add(format!("IFCAXIS2PLACEMENT3D(#{},$,$)", origin));
+        let solid = self.add(format!("IFCEXTRUDEDAREASOLID(#{},#{},#{},{})", profile, axes, up, real(height as f64)));
+        let representation = self.add(format!("IFCSHAPEREPRESENTATION(#{},'Body','SweptSolid',(#{}))", context, solid));
+        self.add(format!("IFCPRODUCTDEFINITIONSHAPE($,$,(#{}))", representation))
+    }
+
+    fn quantities(&mut self, element: usize, name: &str, quantities: &[usize]) {
+        let guid = self.guid();
+        let quantity_set = self.add(format!(
+            "IFCELEMENTQUANTITY('{}',$,'{}',$,$,({}))",
+            guid,
+            name,
+            references(quantities)
+        ));
+        let guid = self.guid();
+        self.add(format!(
+            "IFCRELDEFINESBYPROPERTIES('{}',$,$,$,(#{}),#{})",
+            guid, element, quantity_set
+        ));
+    }
+}
+
+// IFC reals need a decimal point, which `{:?}` of a f64 always writes.
+fn real(value: f64) -> String {
+    format!("{:?}", value)
+}
+
+fn square_metres(length: usize, width: usize) -> String {
+    real((length * width) as f64 / 1_000_000.0)
+}
+
+fn references(entities: &[usize]) -> String {
+    entities
+        .iter()
+        .map(|entity| format!("#{}", entity))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+// Quotes doubled, characters beyond ASCII as \X2\ hexadecimal UTF-16.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\'' => escaped.push_str("''"),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_ascii() && !c.is_ascii_control() => escaped.push(c),
+            c => {
+                escaped.push_str("\\X2\\");
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    escaped.push_str(&format!("{:04X}", unit));
+                }
+                escaped.push_str("\\X0\\");
+            }
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+use crate::calepinage::{Line, Plank};
+#[cfg(test)]
+use crate::plank_line;
+
+#[test]
+fn ifc_should_have_a_member_per_plank_in_a_covering() {
+    let deck = Deck::new(3000, 2).unwrap();
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(2000).unwrap().with_id("B-0412"), Plank::new(1000).unwrap()])
+        .with_line(plank_line![Plank::new(1000).unwrap(), Plank::new(2000).unwrap()]);
+
+    let ifc = to_ifc(&deck, &calepinage, &DeckModel::default(), "Ipé", 1);
+
+    assert!(ifc.starts_with("ISO-10303-21;"));
+    assert_eq!(1, ifc.matches("=IFCCOVERING(").count());
+    assert_eq!(4, ifc.matches("=IFCMEMBER(").count());
+    // the covering takes the shape of its planks rather than a body of its own
+    assert_eq!(4, ifc.matches("=IFCEXTRUDEDAREASOLID(").count());
+    assert!(ifc.contains(",'B-0412','line 1, length 2000 mm',"));
+    assert!(ifc.contains("IFCMATERIAL('Ip\\X2\\00E9\\X0\\',$,$)"));
+    assert!(ifc.contains("IFCQUANTITYAREA('NetArea',$,$,0.28,$)"));
+    assert_eq!(ifc, to_ifc(&deck, &calepinage, &DeckModel::default(), "Ipé", 1));
+}
+
+#[test]
+fn ifc_of_other_layouts_should_have_other_identifiers() {
+    let deck = Deck::new(3000, 1).unwrap();
+    let guids = |calepinage: &Calepinage| {
+        let ifc = to_ifc(&deck, calepinage, &DeckModel::default(), "Ipé", 1);
+        ifc.lines()
+            .filter_map(|entity| entity.split_once("=IFCPROJECT('"))
+            .map(|(_, rest)| rest[..22].to_string())
+            .collect::<Vec<String>>()
+    };
+    let whole = Calepinage::default().with_line(plank_line![Plank::new(3000).unwrap()]);
+    let cut = Calepinage::default().with_line(plank_line![Plank::new(2000).unwrap(), Plank::new(1000).unwrap()]);
+
+    assert_eq!(1, guids(&whole).len());
+    assert_ne!(guids(&whole), guids(&cut));
+}
+
+#[test]
+fn guid_should_be_22_characters_of_ifc_base_64() {
+    let mut step = Step {
+        entities: vec![],
+        guids: Random::new(7),
+    };
+
+    let guid = step.guid();
+
+    assert_eq!(22, guid.len());
+    assert!("0123".contains(&guid[..1]));
+    assert_ne!(guid, step.guid());
+}
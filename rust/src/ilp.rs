@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+
+use good_lp::{
+    microlp, Expression, ProblemVariables, ResolutionError, Solution, SolverModel, Variable,
+};
+
+use crate::calepinage::{count_lengths, gcd, Calepinage, CalepinageError, Deck, Line, Plank, PlankHeap};
+use crate::control::Control;
+use crate::rules::Rules;
+
+/// What a cutting plan minimises.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Objective {
+    /// Total length of the offcuts.
+    Waste,
+    /// Price of the planks used, by plank length; lengths without a price cost nothing.
+    Cost(HashMap<usize, f64>),
+}
+
+/// A piece laid on a line, cut from a plank of the heap (or the whole plank when lengths are equal).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cut {
+    pub line: usize,
+    pub stock: Plank,
+    pub piece: Plank,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CutPlan {
+    pub calepinage: Calepinage,
+    pub cuts: Vec<Cut>,
+    pub objective: f64,
+}
+
+/// Lays the deck allowing planks to be cut, choosing the planks with an integer linear program
+/// so that the objective is minimal. Each plank gives at most one piece, the rest is waste.
+/// Lengths are counted in the greatest common divisor of the deck and plank lengths,
+/// which bounds the size of the program: this is meant for small decks.
+pub fn calepine_with_cuts(
+    plank_heap: PlankHeap,
+    deck: Deck,
+    rules: &Rules,
+    objective: &Objective,
+) -> Result<CutPlan, CalepinageError> {
+    calepine_with_cuts_and_control(plank_heap, deck, rules, objective, &mut Control::default())
+        .map(|plan| plan.expect("a solver without control is not interrupted"))
+}
+
+/// Like `calepine_with_cuts`, checking `control` before each solve, a solve once started
+/// running to its end. When interrupted, no plan following the rules has been found: it is `None`.
+pub fn calepine_with_cuts_and_control(
+    plank_heap: PlankHeap,
+    deck: Deck,
+    rules: &Rules,
+    objective: &Objective,
+    control: &mut Control,
+) -> Result<Option<CutPlan>, CalepinageError> {
+    let model = Model::new(&plank_heap, &deck);
+    let mut exclusions: Vec<Exclusion> = vec![];
+    // staircases and repeated lines are excluded once found in a solution,
+    // instead of adding a constraint for every possible one up front
+    loop {
+        if control.is_interrupted() {
+            return Ok(None);
+        }
+        let plan = model.solve(rules, objective, &exclusions, &plank_heap)?;
+        match model.exclusion(&plan.calepinage, rules) {
+            Some(exclusion) => exclusions.push(exclusion),
+            None => {
+                control.report_layout(&plan.calepinage, &plank_heap);
+                return Ok(Some(plan));
+            }
+        }
+    }
+}
+
+// Segments, in units, a line of the solution must not all use.
+type Exclusion = Vec<(usize, usize, usize)>;
+
+// A line is a path of segments from 0 to `positions`; a segment is a piece from `start` to `end`,
+// cut from a plank of one of the distinct `stocks` lengths.
+struct Model {
+    unit: usize,
+    positions: usize,
+    width: usize,
+    // distinct plank lengths in units, with the number of planks of that length
+    stocks: Vec<(usize, usize)>,
+}
+
+// line, start, end, index in stocks
+type Segment = (usize, usize, usize, usize);
+
+impl Model {
+    fn new(plank_heap: &PlankHeap, deck: &Deck) -> Self {
+        let usable: Vec<usize> = plank_heap
+            .planks()
+            .iter()
+            .map(|plank| plank.length)
+            .filter(|&length| length > 0)
+            .collect();
+        let unit = usable.iter().fold(deck.length, |unit, &length| gcd(unit, length));
+        let stocks = count_lengths(usable.into_iter().map(|length| length / unit));
+        Model {
+            unit,
+            positions: deck.length / unit,
+            width: deck.width,
+            stocks,
+        }
+    }
+
+    fn segments(&self) -> Vec<Segment> {
+        let mut segments = vec![];
+        for line in 0..self.width {
+            for start in 0..self.positions {
+                for end in start + 1..=self.positions {
+                    for (stock, (length, _)) in self.stocks.iter().enumerate() {
+                        if end - start <= *length {
+                            segments.push((line, start, end, stock));
+                        }
+                    }
+                }
+            }
+        }
+        segments
+    }
+
+    // The objective is the length of the planks used, which differs from the waste by the
+    // constant area of the deck; the plan reports the waste itself.
+    fn solve(
+        &self,
+        rules: &Rules,
+        objective: &Objective,
+        exclusions: &[Exclusion],
+        plank_heap: &PlankHeap,
+    ) -> Result<CutPlan, CalepinageError> {
+        let segments = self.segments();
+        let mut variables = ProblemVariables::new();
+        let chosen: Vec<Variable> = segments
+            .iter()
+            .map(|_| variables.add(good_lp::variable().binary()))
+            .collect();
+        let price = |stock: usize| {
+            let length = self.stocks[stock].0 * self.unit;
+            match objective {
+                Objective::Waste => length as f64,
+                Objective::Cost(prices) => prices.get(&length).cloned().unwrap_or(0.0),
+            }
+        };
+        let total: Expression = segments
+            .iter()
+            .zip(&chosen)
+            .map(|((_, _, _, stock), variable)| price(*stock) * *variable)
+            .sum();
+        let sum_of = |keep: &dyn Fn(&Segment) -> bool| -> Expression {
+            segments
+                .iter()
+                .zip(&chosen)
+                .filter(|(segment, _)| keep(segment))
+                .map(|(_, variable)| *variable)
+                .sum()
+        };
+
+        let starting = |line: usize, position: usize| {
+            sum_of(&|&(l, start, _, _)| l == line && start == position)
+        };
+
+        let mut problem = variables.minimise(total).using(microlp);
+        for line in 0..self.width {
+            problem.add_constraint(sum_of(&|&(l, start, _, _)| l == line && start == 0).eq(1));
+            for position in 1..self.positions {
+                let ending = sum_of(&|&(l, _, end, _)| l == line && end == position);
+                problem.add_constraint(ending.eq(starting(line, position)));
+            }
+        }
+        for (stock, (_, count)) in self.stocks.iter().enumerate() {
+            problem.add_constraint(sum_of(&|&(_, _, _, s)| s == stock).leq(*count as f64));
+        }
+        let stagger = &rules.stagger;
+        for line in 0..self.width {
+            for earlier in line.saturating_sub(stagger.lines)..line {
+                for position in 1..self.positions {
+                    for earlier_position in 1..self.positions {
+                        if position.abs_diff(earlier_position) * self.unit <= stagger.tolerance {
+                            problem.add_constraint(
+                                (starting(line, position) + starting(earlier, earlier_position))
+                                .leq(1),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        for exclusion in exclusions {
+            let used = sum_of(&|&(l, start, end, _)| exclusion.contains(&(l, start, end)));
+            problem.add_constraint(used.leq(exclusion.len() as f64 - 1.0));
+        }
+
+        let solution = problem.solve().map_err(|error| match error {
+            ResolutionError::Infeasible => CalepinageError::Infeasible,
+            error => CalepinageError::Solver(error.to_string()),
+        })?;
+        let used: Vec<&Segment> = segments
+            .iter()
+            .zip(&chosen)
+            .filter(|(_, variable)| solution.value(**variable) > 0.5)
+            .map(|(segment, _)| segment)
+            .collect();
+        Ok(self.plan(&used, plank_heap, objective))
+    }
+
+    fn plan(&self, used: &[&Segment], plank_heap: &PlankHeap, objective: &Objective) -> CutPlan {
+        let mut stock_planks: Vec<Plank> = plank_heap.planks().to_vec();
+        let mut calepinage = Calepinage::default();
+        let mut cuts = vec![];
+        let mut value = 0.0;
+        for line_index in 0..self.width {
+            let mut line = Line::default();
+            let mut position = 0;
+            while position < self.positions {
+                let &&(_, _, end, stock) = used
+                    .iter()
+                    .find(|(l, start, _, _)| *l == line_index && *start == position)
+                    .expect("a piece starts where the previous one ends");
+                let stock_length = self.stocks[stock].0 * self.unit;
+                let index = stock_planks
+                    .iter()
+                    .position(|plank| plank.length == stock_length)
+                    .expect("lines use no more planks than the heap has");
+                let stock_plank = stock_planks.remove(index);
+                // the piece keeps the id and metadata of the board it is cut from
+                let piece = Plank {
+                    length: (end - position) * self.unit,
+                    ..stock_plank.clone()
+                };
+                value += match objective {
+                    Objective::Waste => (stock_plank.length - piece.length) as f64,
+                    Objective::Cost(prices) => prices.get(&stock_plank.length).cloned().unwrap_or(0.0),
+                };
+                cuts.push(Cut {
+                    line: line_index,
+                    stock: stock_plank,
+                    piece: piece.clone(),
+                });
+                line = line.with_plank(piece);
+                position = end;
+            }
+            calepinage = calepinage.with_line(line);
+        }
+        CutPlan {
+            calepinage,
+            cuts,
+            objective: value,
+        }
+    }
+
+    // Constraint excluding the first staircase or repeated line of a solution, if any.
+    fn exclusion(&self, calepinage: &Calepinage, rules: &Rules) -> Option<Exclusion> {
+        let Calepinage(lines) = calepinage;
+        let segments_of = |line_index: usize| {
+            let mut position = 0;
+            lines[line_index]
+                .lengths()
+                .into_iter()
+                .map(|length| {
+                    let start = position;
+                    position += length / self.unit;
+                    (line_index, start, position)
+                })
+                .collect::<Exclusion>()
+        };
+        let staircase = rules.staircase.as_ref().and_then(|staircase| {
+            let found = staircase.find_staircases(calepinage).into_iter().next()?;
+            Some(
+                (found.first_line..found.first_line + staircase.lines)
+                    .flat_map(segments_of)
+                    .collect(),
+            )
+        });
+        let repetition = || {
+            rules.repetition.as_ref().and_then(|repetition| {
+                let line = repetition.find_violation(calepinage)?;
+                let earlier = (line.saturating_sub(repetition.lines)..line)
+                    .find(|&earlier| lines[earlier].looks_like(&lines[line]))?;
+                Some([segments_of(line), segments_of(earlier)].concat())
+            })
+        };
+        staircase.or_else(repetition)
+    }
+}
+
+#[cfg(test)]
+use crate::rules::{RepetitionRule, StaggerRule};
+
+#[test]
+fn ilp_should_cut_planks_longer_than_the_deck() {
+    let deck = Deck::new(6, 2).unwrap();
+    let plank_heap = PlankHeap::default().add(1, 10).add(1, 4).add(1, 2);
+
+    let plan = calepine_with_cuts(plank_heap, deck, &Rules::default(), &Objective::Waste).unwrap();
+
+    assert!(Rules::default().accept(&plan.calepinage));
+    assert_eq!(4.0, plan.objective);
+    assert_eq!(plan.calepinage.0.len(), 2);
+}
+
+#[test]
+fn ilp_should_minimise_cost() {
+    let deck = Deck::new(4, 1).unwrap();
+    let plank_heap = PlankHeap::default().add(1, 4).add(2, 2);
+    let prices: HashMap<usize, f64> = [(4, 10.0), (2, 3.0)].iter().cloned().collect();
+
+    let plan = calepine_with_cuts(plank_heap, deck, &Rules::default(), &Objective::Cost(prices)).unwrap();
+
+    assert_eq!(6.0, plan.objective);
+    assert_eq!(vec![2, 2], plan.calepinage.0[0].lengths());
+}
+
+#[test]
+fn ilp_should_follow_stagger_and_repetition() {
+    let deck = Deck::new(6, 3).unwrap();
+    let plank_heap = PlankHeap::default().add(6, 3).add(3, 2).add(3, 4).add(1, 1).add(1, 5);
+    let rules = Rules::default()
+        .with_stagger(StaggerRule::new(1, 0).unwrap())
+        .with_repetition(RepetitionRule::new(2).unwrap());
+
+    let plan = calepine_with_cuts(plank_heap, deck, &rules, &Objective::Waste).unwrap();
+
+    assert!(rules.accept(&plan.calepinage));
+    assert_eq!(0.0, plan.objective);
+}
+
+#[test]
+fn ilp_should_prove_infeasibility() {
+    let deck = Deck::new(4, 2).unwrap();
+    let plank_heap = PlankHeap::default().add(1, 4).add(1, 3);
+
+    let result = calepine_with_cuts(plank_heap, deck, &Rules::default(), &Objective::Waste);
+
+    assert_eq!(Err(CalepinageError::Infeasible), result.map(|plan| plan.calepinage));
+}
+
+#[test]
+fn ilp_should_not_solve_once_cancelled() {
+    use crate::control::CancellationToken;
+
+    let deck = Deck::new(6, 2).unwrap();
+    let plank_heap = PlankHeap::default().add(1, 10).add(1, 4).add(1, 2);
+    let token = CancellationToken::new();
+    token.cancel();
+    let mut control = Control::default().with_cancellation(token);
+
+    let plan = calepine_with_cuts_and_control(plank_heap, deck, &Rules::default(), &Objective::Waste, &mut control);
+
+    assert_eq!(Ok(None), plan);
+}
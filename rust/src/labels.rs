@@ -0,0 +1,209 @@
+use qrcodegen::{QrCode, QrCodeEcc};
+
+use crate::calepinage::{Calepinage, Plank};
+
+/// Layout of a page of sticky labels, in millimetres.
+/// The default is an A4 page of 3 × 8 labels of 70 × 37 mm.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelSheet {
+    pub page_width: f64,
+    pub page_height: f64,
+    pub columns: usize,
+    pub rows: usize,
+    pub label_width: f64,
+    pub label_height: f64,
+    pub margin_left: f64,
+    pub margin_top: f64,
+}
+
+impl Default for LabelSheet {
+    fn default() -> Self {
+        LabelSheet {
+            page_width: 210.0,
+            page_height: 297.0,
+            columns: 3,
+            rows: 8,
+            label_width: 70.0,
+            label_height: 37.0,
+            margin_left: 0.0,
+            margin_top: 0.5,
+        }
+    }
+}
+
+/// What a label tells about a placed plank; lines and positions count from 1, as on site.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub id: String,
+    pub line: usize,
+    pub position: usize,
+    pub length: usize,
+}
+
+impl Label {
+    /// Text encoded in the QR code of the label.
+    pub fn qr_text(&self) -> String {
+        format!(
+            "id={};line={};position={};length={}",
+            self.id, self.line, self.position, self.length
+        )
+    }
+}
+
+/// A label per plank of the calepinage, line after line. Planks without id are named
+/// after their line and position.
+pub fn labels(calepinage: &Calepinage) -> Vec<Label> {
+    let Calepinage(lines) = calepinage;
+    lines
+        .iter()
+        .enumerate()
+        .flat_map(|(line_index, line)| {
+            line.0.iter().enumerate().map(move |(position, plank): (usize, &Plank)| Label {
+                id: plank
+                    .id
+                    .clone()
+                    .unwrap_or_else(|| format!("L{}-P{}", line_index + 1, position + 1)),
+                line: line_index + 1,
+                position: position + 1,
+                length: plank.length,
+            })
+        })
+        .collect()
+}
+
+/// Pages of labels as SVG documents sized in millimetres, ready to print on `sheet`,
+/// which must have at least a column and a row of labels.
+pub fn label_pages_svg(calepinage: &Calepinage, sheet: &LabelSheet) -> Result<Vec<String>, String> {
+    if sheet.columns == 0 || sheet.rows == 0 {
+        return Err(format!(
+            "a label sheet has at least a column and a row, got {} × {}",
+            sheet.columns, sheet.rows
+        ));
+    }
+    let per_page = sheet.columns * sheet.rows;
+    let pages = labels(calepinage)
+        .chunks(per_page)
+        .map(|page| {
+            let mut svg = format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" viewBox=\"0 0 {w} {h}\">\n",
+                w = sheet.page_width,
+                h = sheet.page_height
+            );
+            for (index, label) in page.iter().enumerate() {
+                let x = sheet.margin_left + (index % sheet.columns) as f64 * sheet.label_width;
+                let y = sheet.margin_top + (index / sheet.columns) as f64 * sheet.label_height;
+                write_label(&mut svg, label, x, y, sheet);
+            }
+            svg.push_str("</svg>\n");
+            svg
+        })
+        .collect();
+    Ok(pages)
+}
+
+fn write_label(svg: &mut String, label: &Label, x: f64, y: f64, sheet: &LabelSheet) {
+    let padding = 2.0;
+    let qr_size = sheet.label_height - 2.0 * padding;
+    let text_x = x + qr_size + 2.0 * padding;
+    svg.push_str("<g>\n");
+    write_qr_code(svg, &label.qr_text(), x + padding, y + padding, qr_size);
+    svg.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"6\" font-weight=\"bold\">{}</text>\n",
+        text_x,
+        y + padding + 6.0,
+        escape(&label.id)
+    ));
+    svg.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"4\">line {}, position {}</text>\n",
+        text_x,
+        y + padding + 14.0,
+        label.line,
+        label.position
+    ));
+    svg.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"4\">cut {} mm</text>\n",
+        text_x,
+        y + padding + 20.0,
+        label.length
+    ));
+    svg.push_str("</g>\n");
+}
+
+// The QR code as one path of its dark modules, with the quiet zone inside `size`.
+fn write_qr_code(svg: &mut String, text: &str, x: f64, y: f64, size: f64) {
+    let qr = match QrCode::encode_text(text, QrCodeEcc::Medium) {
+        Ok(qr) => qr,
+        Err(_) => return,
+    };
+    let quiet_zone = 2;
+    let modules = qr.size() + 2 * quiet_zone;
+    let module = size / modules as f64;
+    let mut path = String::new();
+    for row in 0..qr.size() {
+        for column in 0..qr.size() {
+            if qr.get_module(column, row) {
+                path.push_str(&format!(
+                    "M{:.3} {:.3}h{:.3}v{:.3}h-{:.3}z",
+                    x + (column + quiet_zone) as f64 * module,
+                    y + (row + quiet_zone) as f64 * module,
+                    module,
+                    module,
+                    module
+                ));
+            }
+        }
+    }
+    svg.push_str(&format!("<path d=\"{}\" fill=\"black\"/>\n", path));
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+use crate::calepinage::Line;
+#[cfg(test)]
+use crate::plank_line;
+
+#[test]
+fn labels_should_name_planks_without_id_after_their_place() {
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(1200).unwrap().with_id("B-0412"), Plank::new(800).unwrap()]);
+
+    let labels = labels(&calepinage);
+
+    assert_eq!("id=B-0412;line=1;position=1;length=1200", labels[0].qr_text());
+    assert_eq!("L1-P2", labels[1].id);
+}
+
+#[test]
+fn label_pages_should_hold_a_label_per_plank() {
+    let mut calepinage = Calepinage::default();
+    for _ in 0..13 {
+        calepinage = calepinage.with_line(plank_line![Plank::new(1000).unwrap(), Plank::new(1000).unwrap()]);
+    }
+
+    let pages = label_pages_svg(&calepinage, &LabelSheet::default()).unwrap();
+
+    assert_eq!(2, pages.len());
+    assert_eq!(24, pages[0].matches("<g>").count());
+    assert_eq!(2, pages[1].matches("<g>").count());
+    assert!(pages[1].contains("line 13, position 2"));
+}
+
+#[test]
+fn label_pages_should_need_a_column_and_a_row() {
+    let calepinage = Calepinage::default().with_line(plank_line![Plank::new(1000).unwrap()]);
+    let sheet = LabelSheet {
+        columns: 0,
+        ..LabelSheet::default()
+    };
+
+    assert_eq!(
+        Err("a label sheet has at least a column and a row, got 0 × 8".to_string()),
+        label_pages_svg(&calepinage, &sheet)
+    );
+}
@@ -1 +1,28 @@
+pub mod beam;
 pub mod calepinage;
+pub mod control;
+pub mod dxf;
+pub mod enumerate;
+pub mod geometry;
+pub mod ifc;
+#[cfg(feature = "ilp")]
+pub mod ilp;
+#[cfg(feature = "labels")]
+pub mod labels;
+pub mod metrics;
+pub mod obj;
+pub mod optimise;
+pub mod outline;
+pub mod pareto;
+#[cfg(feature = "pdf")]
+pub mod pdf;
+pub mod precheck;
+pub mod random;
+#[cfg(feature = "raster")]
+pub mod raster;
+pub mod rules;
+#[cfg(feature = "sat")]
+pub mod sat;
+pub mod score;
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
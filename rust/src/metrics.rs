@@ -0,0 +1,67 @@
+use crate::calepinage::{Calepinage, Line};
+
+/// Smallest distance between a junction and a junction of the next line, `None` without such pair.
+pub fn min_stagger(calepinage: &Calepinage) -> Option<usize> {
+    let Calepinage(lines) = calepinage;
+    lines
+        .windows(2)
+        .filter_map(|pair| stagger_between(&pair[0], &pair[1]))
+        .min()
+}
+
+/// Smallest distance between junctions of two lines, `None` when one of them has no junction.
+pub fn stagger_between(lhs: &Line, rhs: &Line) -> Option<usize> {
+    let rhs_junctions = rhs.compute_junction();
+    lhs.compute_junction()
+        .iter()
+        .flat_map(|left| rhs_junctions.iter().map(move |right| left.distance(right)))
+        .min()
+}
+
+/// Share of lines copying an earlier line, as is or mirrored:
+/// 0 when every line is unique, 1 when all lines look the same.
+pub fn repetition(calepinage: &Calepinage) -> f64 {
+    let Calepinage(lines) = calepinage;
+    if lines.len() < 2 {
+        return 0.0;
+    }
+    let repeated = (1..lines.len())
+        .filter(|&index| lines[..index].iter().any(|earlier| earlier.looks_like(&lines[index])))
+        .count();
+    repeated as f64 / (lines.len() - 1) as f64
+}
+
+#[cfg(test)]
+use crate::calepinage::Plank;
+#[cfg(test)]
+use crate::plank_line;
+
+#[test]
+fn unique_lines_should_not_be_repetitive() {
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(3).unwrap(), Plank::new(1).unwrap()])
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(2).unwrap()]);
+
+    assert_eq!(0.0, repetition(&calepinage));
+}
+
+#[test]
+fn mirrored_lines_should_count_as_repeated() {
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(3).unwrap(), Plank::new(1).unwrap()])
+        .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(3).unwrap()])
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(2).unwrap()]);
+
+    assert_eq!(0.5, repetition(&calepinage));
+}
+
+#[test]
+fn min_stagger_should_look_at_adjacent_lines_only() {
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(3).unwrap(), Plank::new(3).unwrap()])
+        .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(5).unwrap()])
+        .with_line(plank_line![Plank::new(4).unwrap(), Plank::new(2).unwrap()]);
+
+    assert_eq!(Some(2), min_stagger(&calepinage));
+    assert_eq!(None, min_stagger(&Calepinage::default()));
+}
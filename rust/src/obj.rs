@@ -0,0 +1,162 @@
+use crate::calepinage::{Calepinage, Deck};
+use crate::geometry::Geometry;
+
+/// Joists under the deck, across the lines from x = 0 every `spacing`, in millimetres.
+/// A deck shorter than a joist is wide has none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joists {
+    pub spacing: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Dimensions of the 3D model, in millimetres: lines of planks `plank_width` wide and
+/// `thickness` thick, `gap` apart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeckModel {
+    pub plank_width: usize,
+    pub thickness: usize,
+    pub gap: usize,
+    pub joists: Option<Joists>,
+}
+
+impl Default for DeckModel {
+    fn default() -> Self {
+        DeckModel {
+            plank_width: 140,
+            thickness: 27,
+            gap: 5,
+            joists: None,
+        }
+    }
+}
+
+/// Wavefront OBJ mesh of the deck, a box per plank and per joist, in millimetres with y up:
+/// lines run along x and follow each other along z, the planks lying on y = 0.
+pub fn to_obj(deck: &Deck, calepinage: &Calepinage, model: &DeckModel) -> String {
+    let geometry = Geometry::new(calepinage, model.plank_width);
+    let pitch = (model.plank_width + model.gap) as f64;
+    let mut obj = Obj::default();
+    obj.comment("calepinage, millimetres");
+
+    obj.group("planks");
+    for plank in &geometry.planks {
+        let name = plank.plank.id.clone().unwrap_or_else(|| format!("plank_{}", plank.index + 1));
+        let z = plank.line as f64 * pitch;
+        obj.cuboid(
+            &name,
+            [plank.x_start as f64, 0.0, z],
+            [plank.x_end as f64, model.thickness as f64, z + model.plank_width as f64],
+        );
+    }
+
+    let fitting = |joists: &&Joists| joists.spacing > 0 && joists.width <= deck.length;
+    if let Some(joists) = model.joists.as_ref().filter(fitting) {
+        obj.group("joists");
+        let deck_width = deck.width as f64 * pitch - model.gap as f64;
+        for (index, x) in (0..=deck.length).step_by(joists.spacing).enumerate() {
+            let half = joists.width as f64 / 2.0;
+            let x = (x as f64).clamp(half, deck.length as f64 - half);
+            obj.cuboid(
+                &format!("joist_{}", index + 1),
+                [x - half, -(joists.height as f64), 0.0],
+                [x + half, 0.0, deck_width],
+            );
+        }
+    }
+    obj.text
+}
+
+#[derive(Default)]
+struct Obj {
+    text: String,
+    vertices: usize,
+}
+
+impl Obj {
+    fn comment(&mut self, comment: &str) {
+        self.text.push_str(&format!("# {}\n", comment));
+    }
+
+    fn group(&mut self, name: &str) {
+        self.text.push_str(&format!("g {}\n", name));
+    }
+
+    // Box between two opposite corners, faces wound counter-clockwise seen from outside.
+    fn cuboid(&mut self, name: &str, min: [f64; 3], max: [f64; 3]) {
+        self.text.push_str(&format!("o {}\n", name.replace(char::is_whitespace, "_")));
+        for &z in [min[2], max[2]].iter() {
+            for &(x, y) in [(min[0], min[1]), (max[0], min[1]), (max[0], max[1]), (min[0], max[1])].iter() {
+                self.text.push_str(&format!("v {} {} {}\n", x, y, z));
+            }
+        }
+        let faces = [
+            [1, 4, 3, 2],
+            [5, 6, 7, 8],
+            [1, 2, 6, 5],
+            [2, 3, 7, 6],
+            [3, 4, 8, 7],
+            [4, 1, 5, 8],
+        ];
+        for face in faces.iter() {
+            self.text.push_str(&format!(
+                "f {} {} {} {}\n",
+                self.vertices + face[0],
+                self.vertices + face[1],
+                self.vertices + face[2],
+                self.vertices + face[3]
+            ));
+        }
+        self.vertices += 8;
+    }
+}
+
+#[cfg(test)]
+use crate::calepinage::{Line, Plank};
+#[cfg(test)]
+use crate::plank_line;
+
+#[test]
+fn obj_should_have_a_box_per_plank_and_joist() {
+    let deck = Deck::new(3000, 2).unwrap();
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(2000).unwrap().with_id("B-0412"), Plank::new(1000).unwrap()])
+        .with_line(plank_line![Plank::new(1000).unwrap(), Plank::new(2000).unwrap()]);
+    let model = DeckModel {
+        joists: Some(Joists {
+            spacing: 500,
+            width: 45,
+            height: 70,
+        }),
+        ..DeckModel::default()
+    };
+
+    let obj = to_obj(&deck, &calepinage, &model);
+
+    assert_eq!(4 + 7, obj.matches("\no ").count());
+    assert_eq!(8 * 11, obj.matches("\nv ").count());
+    assert_eq!(6 * 11, obj.matches("\nf ").count());
+    assert!(obj.contains("o B-0412\n"));
+    // second line starts one plank width and one gap away
+    assert!(obj.contains("v 0 0 145\n"));
+    assert!(obj.contains("f 81 84 83 82\n"));
+}
+
+#[test]
+fn obj_should_have_no_joist_wider_than_the_deck() {
+    let deck = Deck::new(30, 1).unwrap();
+    let calepinage = Calepinage::default().with_line(plank_line![Plank::new(30).unwrap()]);
+    let model = DeckModel {
+        joists: Some(Joists {
+            spacing: 500,
+            width: 45,
+            height: 70,
+        }),
+        ..DeckModel::default()
+    };
+
+    let obj = to_obj(&deck, &calepinage, &model);
+
+    assert!(!obj.contains("joist"));
+    assert_eq!(1, obj.matches("\no ").count());
+}
@@ -0,0 +1,323 @@
+use crate::calepinage::{Calepinage, Line};
+use crate::control::{Control, Outcome, Progress};
+use crate::metrics::stagger_between;
+use crate::random::Random;
+use crate::rules::Rules;
+
+// Stagger of a layout: its smallest stagger between neighbour lines,
+// then how many neighbour lines share it (the fewer, the better).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StaggerProfile {
+    min: usize,
+    count: usize,
+}
+
+impl StaggerProfile {
+    const NONE: StaggerProfile = StaggerProfile {
+        min: usize::MAX,
+        count: 0,
+    };
+
+    fn with_pair(self, stagger: Option<usize>) -> Self {
+        match stagger {
+            Some(stagger) if stagger < self.min => StaggerProfile {
+                min: stagger,
+                count: 1,
+            },
+            Some(stagger) if stagger == self.min => StaggerProfile {
+                count: self.count + 1,
+                ..self
+            },
+            _ => self,
+        }
+    }
+
+    fn is_better_than(&self, other: &StaggerProfile) -> bool {
+        self.min > other.min || (self.min == other.min && self.count < other.count)
+    }
+
+    fn of(lines: &[Line]) -> Self {
+        lines
+            .windows(2)
+            .fold(StaggerProfile::NONE, |profile, pair| {
+                profile.with_pair(stagger_between(&pair[0], &pair[1]))
+            })
+    }
+}
+
+/// Reverses lines and swaps them to push junctions of neighbour lines apart,
+/// keeping every plank and every rule the layout already followed.
+pub fn improve_stagger(calepinage: Calepinage, rules: &Rules) -> Calepinage {
+    let followed_rules = rules.accept(&calepinage);
+    let is_acceptable = |lines: &[Line]| !followed_rules || rules.accept(&Calepinage(lines.to_vec()));
+    let Calepinage(mut lines) = calepinage;
+
+    let mut profile = StaggerProfile::of(&lines);
+    if let Some(oriented) = Some(orient(&lines)).filter(|oriented| is_acceptable(oriented)) {
+        let oriented_profile = StaggerProfile::of(&oriented);
+        if oriented_profile.is_better_than(&profile) {
+            lines = oriented;
+            profile = oriented_profile;
+        }
+    }
+
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for first in 0..lines.len() {
+            for second in first + 1..lines.len() {
+                let mut swapped = lines.clone();
+                swapped.swap(first, second);
+                let candidate = orient(&swapped);
+                let candidate_profile = StaggerProfile::of(&candidate);
+                if candidate_profile.is_better_than(&profile) && is_acceptable(&candidate) {
+                    lines = candidate;
+                    profile = candidate_profile;
+                    improved = true;
+                }
+            }
+        }
+    }
+    Calepinage(lines)
+}
+
+// Best way to reverse lines keeping their order: for each line and each direction,
+// the best profile of the lines above it, Viterbi style. Reversing fewer lines wins ties.
+fn orient(lines: &[Line]) -> Vec<Line> {
+    let directions = |line: &Line| [line.clone(), reversed(line)];
+    let is_better = |lhs: &(StaggerProfile, usize), rhs: &(StaggerProfile, usize)| {
+        lhs.0.is_better_than(&rhs.0) || (lhs.0 == rhs.0 && lhs.1 < rhs.1)
+    };
+    // (profile, reversed lines) reached in each direction, and the direction of the line before
+    let mut best: Vec<[((StaggerProfile, usize), usize); 2]> = vec![];
+    for (index, line) in lines.iter().enumerate() {
+        let current = directions(line);
+        let entry = if index == 0 {
+            [((StaggerProfile::NONE, 0), 0), ((StaggerProfile::NONE, 1), 0)]
+        } else {
+            let previous = directions(&lines[index - 1]);
+            let best_from = |direction: usize| {
+                let from_direction = |previous_direction: usize| {
+                    let (profile, reversals) = best[index - 1][previous_direction].0;
+                    (
+                        profile.with_pair(stagger_between(&previous[previous_direction], &current[direction])),
+                        reversals + direction,
+                    )
+                };
+                if is_better(&from_direction(1), &from_direction(0)) {
+                    (from_direction(1), 1)
+                } else {
+                    (from_direction(0), 0)
+                }
+            };
+            [best_from(0), best_from(1)]
+        };
+        best.push(entry);
+    }
+
+    // on ties, reverse the last lines rather than the first ones
+    let mut oriented = Vec::with_capacity(lines.len());
+    let mut direction = match best.last() {
+        Some([keep, reverse]) if !is_better(&keep.0, &reverse.0) => 1,
+        _ => 0,
+    };
+    for index in (0..lines.len()).rev() {
+        oriented.push(directions(&lines[index])[direction].clone());
+        direction = best[index][direction].1;
+    }
+    oriented.reverse();
+    oriented
+}
+
+/// Simulated annealing over a finished layout: `iterations` random moves (swapping planks on a line,
+/// swapping runs of the same length between lines, reversing a line), accepted when they lower
+/// `score` or, less and less often, when they raise it. Returns the best layout met, which follows
+/// every rule the given one followed and uses the same planks.
+pub fn anneal(
+    calepinage: Calepinage,
+    rules: &Rules,
+    score: &dyn Fn(&Calepinage) -> f64,
+    seed: u64,
+    iterations: usize,
+) -> Calepinage {
+    anneal_with_control(calepinage, rules, score, seed, iterations, &mut Control::default()).calepinage
+}
+
+/// Like `anneal`, stopping early with the best layout met when `control` is interrupted,
+/// and reporting progress each time a better layout is met.
+pub fn anneal_with_control(
+    calepinage: Calepinage,
+    rules: &Rules,
+    score: &dyn Fn(&Calepinage) -> f64,
+    seed: u64,
+    iterations: usize,
+    control: &mut Control,
+) -> Outcome {
+    let followed_rules = rules.accept(&calepinage);
+    let mut random = Random::new(seed);
+    let mut current_score = score(&calepinage);
+    let mut current = calepinage;
+    let mut best = (current.clone(), current_score);
+    let start_temperature = current_score.abs().max(1.0) / 10.0;
+
+    for iteration in 0..iterations {
+        if control.is_interrupted() {
+            return Outcome {
+                calepinage: best.0,
+                interrupted: true,
+            };
+        }
+        let candidate = match random_move(&current, &mut random) {
+            Some(candidate) if !followed_rules || rules.accept(&candidate) => candidate,
+            _ => continue,
+        };
+        let candidate_score = score(&candidate);
+        let temperature = start_temperature * (1.0 - iteration as f64 / iterations as f64);
+        let delta = candidate_score - current_score;
+        if delta <= 0.0 || random.unit() < (-delta / temperature).exp() {
+            current = candidate;
+            current_score = candidate_score;
+            if current_score < best.1 {
+                best = (current.clone(), current_score);
+                control.report(Progress {
+                    lines_completed: best.0.0.len(),
+                    best_score: Some(best.1),
+                });
+            }
+        }
+    }
+    Outcome {
+        calepinage: best.0,
+        interrupted: false,
+    }
+}
+
+fn random_move(calepinage: &Calepinage, random: &mut Random) -> Option<Calepinage> {
+    let Calepinage(lines) = calepinage;
+    if lines.is_empty() {
+        return None;
+    }
+    let mut lines = lines.clone();
+    let line = random.below(lines.len());
+    match random.below(3) {
+        0 => {
+            let planks = &mut lines[line].0;
+            if planks.len() < 2 {
+                return None;
+            }
+            let first = random.below(planks.len());
+            let second = random.below(planks.len());
+            if planks[first] == planks[second] {
+                return None;
+            }
+            planks.swap(first, second);
+        }
+        1 => {
+            let other = random.below(lines.len());
+            if other == line || lines[line].0.is_empty() {
+                return None;
+            }
+            let start = random.below(lines[line].0.len());
+            let end = start + 1 + random.below(lines[line].0.len() - start);
+            let run = lines[line].0[start..end].to_vec();
+            let run_length: usize = run.iter().map(|plank| plank.length).sum();
+            let matching: Vec<(usize, usize)> = (0..lines[other].0.len())
+                .flat_map(|other_start| (other_start + 1..=lines[other].0.len()).map(move |other_end| (other_start, other_end)))
+                .filter(|&(other_start, other_end)| {
+                    let other_run = &lines[other].0[other_start..other_end];
+                    other_run != run.as_slice()
+                        && other_run.iter().map(|plank| plank.length).sum::<usize>() == run_length
+                })
+                .collect();
+            if matching.is_empty() {
+                return None;
+            }
+            let (other_start, other_end) = matching[random.below(matching.len())];
+            let other_run: Vec<_> = lines[other].0.splice(other_start..other_end, run).collect();
+            lines[line].0.splice(start..end, other_run);
+        }
+        _ => {
+            let flipped = reversed(&lines[line]);
+            if flipped == lines[line] {
+                return None;
+            }
+            lines[line] = flipped;
+        }
+    }
+    Some(Calepinage(lines))
+}
+
+fn reversed(line: &Line) -> Line {
+    Line(line.0.iter().rev().cloned().collect())
+}
+
+#[cfg(test)]
+use crate::calepinage::Plank;
+#[cfg(test)]
+use crate::plank_line;
+
+#[test]
+fn should_reverse_line_with_aligned_junction() {
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(1).unwrap()])
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(1).unwrap()]);
+
+    let expected = Calepinage::default()
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(1).unwrap()])
+        .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(2).unwrap()]);
+    assert_eq!(expected, improve_stagger(calepinage, &Rules::default()));
+}
+
+#[test]
+fn should_swap_lines_to_separate_junctions() {
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(3).unwrap(), Plank::new(3).unwrap()])
+        .with_line(plank_line![Plank::new(3).unwrap(), Plank::new(3).unwrap()])
+        .with_line(plank_line![Plank::new(6).unwrap()]);
+
+    let Calepinage(lines) = improve_stagger(calepinage, &Rules::default());
+
+    assert_eq!(plank_line![Plank::new(6).unwrap()], lines[1]);
+}
+
+#[cfg(test)]
+use crate::metrics::min_stagger;
+
+#[test]
+fn anneal_should_not_worsen_score() {
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(2).unwrap(), Plank::new(4).unwrap()])
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(2).unwrap(), Plank::new(4).unwrap()])
+        .with_line(plank_line![Plank::new(4).unwrap(), Plank::new(4).unwrap()]);
+    let score = |calepinage: &Calepinage| -(min_stagger(calepinage).unwrap_or(0) as f64);
+
+    let annealed = anneal(calepinage.clone(), &Rules::default(), &score, 7, 500);
+
+    assert!(score(&annealed) < score(&calepinage));
+    assert!(Rules::default().accept(&annealed));
+}
+
+#[test]
+fn anneal_should_keep_rules_followed() {
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(3).unwrap(), Plank::new(1).unwrap(), Plank::new(2).unwrap()])
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(3).unwrap(), Plank::new(1).unwrap()]);
+    // rewards aligned junctions, which the stagger rule forbids
+    let score = |calepinage: &Calepinage| min_stagger(calepinage).unwrap_or(0) as f64;
+
+    let annealed = anneal(calepinage, &Rules::default(), &score, 3, 500);
+
+    assert!(Rules::default().accept(&annealed));
+}
+
+#[test]
+fn anneal_should_leave_empty_lines_alone() {
+    let calepinage = Calepinage::default()
+        .with_line(Line(vec![]))
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(1).unwrap()]);
+    let score = |calepinage: &Calepinage| -(min_stagger(calepinage).unwrap_or(0) as f64);
+
+    let annealed = anneal(calepinage.clone(), &Rules::default(), &score, 5, 500);
+
+    assert_eq!(Line(vec![]), annealed.0[0]);
+}
@@ -0,0 +1,486 @@
+use crate::calepinage::Deck;
+use crate::geometry::{Geometry, PlacedPlank};
+
+/// A point of a plan, in millimetres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Shape of a terrace read from a plan: the largest closed polyline is its boundary,
+/// closed polylines inside it are obstacles (posts, trees, hatches).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outline {
+    pub boundary: Vec<Point>,
+    pub obstacles: Vec<Vec<Point>>,
+}
+
+impl Outline {
+    /// Reads the closed LWPOLYLINE and POLYLINE entities of an ASCII DXF,
+    /// converting from the `$INSUNITS` of its header (millimetres when missing).
+    pub fn from_dxf(dxf: &str) -> Result<Self, String> {
+        let lines: Vec<&str> = dxf.lines().map(str::trim).collect();
+        if !lines.len().is_multiple_of(2) {
+            return Err("DXF group codes and values do not pair up".to_string());
+        }
+        let pairs: Vec<(i32, &str)> = lines
+            .chunks(2)
+            .map(|pair| {
+                pair[0]
+                    .parse::<i32>()
+                    .map(|code| (code, pair[1]))
+                    .map_err(|_| format!("invalid DXF group code {}", pair[0]))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let mm_per_unit = pairs
+            .windows(2)
+            .find(|window| window[0] == (9, "$INSUNITS"))
+            .map(|window| dxf_unit(window[1].1))
+            .unwrap_or(Ok(1.0))?;
+
+        let mut loops = vec![];
+        let mut index = 0;
+        while index < pairs.len() {
+            match pairs[index] {
+                (0, "LWPOLYLINE") => {
+                    let end = next_entity(&pairs, index);
+                    let (points, closed) = polyline(&pairs[index + 1..end])?;
+                    if closed {
+                        loops.push(points);
+                    }
+                    index = end;
+                }
+                (0, "POLYLINE") => {
+                    let end = next_entity(&pairs, index);
+                    let (_, closed) = polyline(&pairs[index + 1..end])?;
+                    let mut points = vec![];
+                    index = end;
+                    while index < pairs.len() && pairs[index] == (0, "VERTEX") {
+                        let end = next_entity(&pairs, index);
+                        points.extend(polyline(&pairs[index + 1..end])?.0);
+                        index = end;
+                    }
+                    if closed || is_closed(&points) {
+                        loops.push(points);
+                    }
+                }
+                _ => index += 1,
+            }
+        }
+        Outline::from_loops(loops, mm_per_unit)
+    }
+
+    /// Reads the `polygon`, closed `polyline`, `rect` and `path` elements of an SVG, in millimetres
+    /// from its size and view box; transforms are not applied. Subpaths with curves
+    /// (C, S, Q, T and A commands) are not polylines and are skipped.
+    pub fn from_svg(svg: &str) -> Result<Self, String> {
+        let mut loops = vec![];
+        let mut mm_per_unit = PX_IN_MM;
+        for tag in svg.split('<').filter_map(|part| part.split('>').next()) {
+            let name = tag.split_whitespace().next().unwrap_or("");
+            match name {
+                "svg" => mm_per_unit = svg_scale(tag)?,
+                "polygon" | "polyline" => {
+                    let points = numbers(attribute(tag, "points").unwrap_or(""))?;
+                    let points: Vec<Point> = points
+                        .chunks(2)
+                        .filter(|pair| pair.len() == 2)
+                        .map(|pair| Point { x: pair[0], y: pair[1] })
+                        .collect();
+                    if name == "polygon" || is_closed(&points) {
+                        loops.push(points);
+                    }
+                }
+                "rect" => {
+                    let number = |name: &str| {
+                        attribute(tag, name)
+                            .and_then(|value| value.trim().parse::<f64>().ok())
+                            .unwrap_or(0.0)
+                    };
+                    let (x, y, width, height) = (number("x"), number("y"), number("width"), number("height"));
+                    loops.push(vec![
+                        Point { x, y },
+                        Point { x: x + width, y },
+                        Point { x: x + width, y: y + height },
+                        Point { x, y: y + height },
+                    ]);
+                }
+                "path" => loops.extend(path_loops(attribute(tag, "d").unwrap_or(""))?),
+                _ => {}
+            }
+        }
+        Outline::from_loops(loops, mm_per_unit)
+    }
+
+    fn from_loops(loops: Vec<Vec<Point>>, mm_per_unit: f64) -> Result<Self, String> {
+        let mut loops: Vec<Vec<Point>> = loops
+            .into_iter()
+            .filter(|points| points.len() >= 3)
+            .map(|points| {
+                points
+                    .into_iter()
+                    .map(|point| Point {
+                        x: point.x * mm_per_unit,
+                        y: point.y * mm_per_unit,
+                    })
+                    .collect()
+            })
+            .collect();
+        if loops.is_empty() {
+            return Err("no closed polyline found".to_string());
+        }
+        loops.sort_by(|lhs, rhs| area(rhs).total_cmp(&area(lhs)));
+        let boundary = loops.remove(0);
+        let obstacles = loops
+            .into_iter()
+            .filter(|obstacle| contains(&boundary, &obstacle[0]))
+            .collect();
+        Ok(Outline { boundary, obstacles })
+    }
+
+    /// Lower left and upper right corners of the boundary.
+    pub fn bounding_box(&self) -> (Point, Point) {
+        self.boundary.iter().fold(
+            (
+                Point { x: f64::MAX, y: f64::MAX },
+                Point { x: f64::MIN, y: f64::MIN },
+            ),
+            |(min, max), point| {
+                (
+                    Point { x: min.x.min(point.x), y: min.y.min(point.y) },
+                    Point { x: max.x.max(point.x), y: max.y.max(point.y) },
+                )
+            },
+        )
+    }
+
+    /// The rectangular deck covering the outline with lines of planks `plank_width` wide
+    /// running along x. Obstacles and the parts of the rectangle outside the boundary
+    /// are left to cut on site, on the planks `planks_to_cut` gives.
+    pub fn to_deck(&self, plank_width: usize) -> Result<Deck, String> {
+        if plank_width == 0 {
+            return Err("plank width must be positive".to_string());
+        }
+        let (min, max) = self.bounding_box();
+        let length = (max.x - min.x).round() as usize;
+        let width = ((max.y - min.y) / plank_width as f64).ceil() as usize;
+        Deck::new(length, width)
+    }
+
+    /// Planks of a layout of the deck of `to_deck` crossing the boundary or an obstacle,
+    /// the deck starting at the lower left corner of the bounding box.
+    pub fn planks_to_cut<'a>(&self, geometry: &'a Geometry) -> Vec<&'a PlacedPlank> {
+        let (min, _) = self.bounding_box();
+        geometry
+            .planks
+            .iter()
+            .filter(|plank| {
+                // planks exactly along the boundary or an obstacle do not cross it
+                let margin = 0.5;
+                let x_start = min.x + plank.x_start as f64 + margin;
+                let x_end = min.x + plank.x_end as f64 - margin;
+                let y_start = min.y + plank.y as f64 + margin;
+                let y_end = min.y + (plank.y + plank.width) as f64 - margin;
+                let rectangle = [
+                    Point { x: x_start, y: y_start },
+                    Point { x: x_end, y: y_start },
+                    Point { x: x_end, y: y_end },
+                    Point { x: x_start, y: y_end },
+                ];
+                let inside_boundary = rectangle.iter().all(|corner| contains(&self.boundary, corner))
+                    && !edges_cross(&self.boundary, &rectangle);
+                !inside_boundary || self.obstacles.iter().any(|obstacle| overlap(obstacle, &rectangle))
+            })
+            .collect()
+    }
+}
+
+// one pixel at 96 dpi
+const PX_IN_MM: f64 = 25.4 / 96.0;
+
+fn dxf_unit(code: &str) -> Result<f64, String> {
+    match code.trim() {
+        "0" | "4" => Ok(1.0),
+        "1" => Ok(25.4),
+        "2" => Ok(304.8),
+        "5" => Ok(10.0),
+        "6" => Ok(1000.0),
+        other => Err(format!("unsupported DXF unit {}", other)),
+    }
+}
+
+fn next_entity(pairs: &[(i32, &str)], index: usize) -> usize {
+    (index + 1..pairs.len())
+        .find(|&next| pairs[next].0 == 0)
+        .unwrap_or(pairs.len())
+}
+
+// Points of the 10/20 codes of an entity, and whether its 70 flag closes it.
+fn polyline(pairs: &[(i32, &str)]) -> Result<(Vec<Point>, bool), String> {
+    let mut points = vec![];
+    let mut closed = false;
+    let parse = |value: &str| value.parse::<f64>().map_err(|_| format!("invalid DXF coordinate {}", value));
+    for (code, value) in pairs {
+        match code {
+            10 => points.push(Point { x: parse(value)?, y: 0.0 }),
+            20 => {
+                if let Some(point) = points.last_mut() {
+                    point.y = parse(value)?;
+                }
+            }
+            70 => closed = value.parse::<i32>().map(|flags| flags & 1 == 1).unwrap_or(false),
+            _ => {}
+        }
+    }
+    Ok((points, closed))
+}
+
+fn is_closed(points: &[Point]) -> bool {
+    points.len() > 3 && points.first() == points.last()
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!("{}=", name);
+    let mut rest = tag;
+    while let Some(found) = rest.find(&pattern) {
+        let preceded_by_space = rest[..found].chars().last().is_some_and(char::is_whitespace);
+        let after = &rest[found + pattern.len()..];
+        if preceded_by_space {
+            let quote = after.chars().next()?;
+            let value = &after[1..];
+            return value.find(quote).map(|end| &value[..end]);
+        }
+        rest = after;
+    }
+    None
+}
+
+fn numbers(text: &str) -> Result<Vec<f64>, String> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse::<f64>().map_err(|_| format!("invalid SVG number {}", part)))
+        .collect()
+}
+
+// Millimetres per user unit of the root element, from its width and view box.
+fn svg_scale(tag: &str) -> Result<f64, String> {
+    let width = match attribute(tag, "width") {
+        Some(width) => width.trim(),
+        None => return Ok(PX_IN_MM),
+    };
+    let split = width.find(|c: char| c.is_alphabetic() || c == '%').unwrap_or(width.len());
+    let value: f64 = width[..split].parse().map_err(|_| format!("invalid SVG width {}", width))?;
+    let mm = value
+        * match &width[split..] {
+            "mm" => 1.0,
+            "cm" => 10.0,
+            "m" => 1000.0,
+            "in" => 25.4,
+            "pt" => 25.4 / 72.0,
+            "" | "px" => PX_IN_MM,
+            unit => return Err(format!("unsupported SVG unit {}", unit)),
+        };
+    let view_box_width = attribute(tag, "viewBox")
+        .map(numbers)
+        .transpose()?
+        .and_then(|view_box| view_box.get(2).cloned());
+    Ok(match view_box_width {
+        Some(view_box_width) if view_box_width > 0.0 => mm / view_box_width,
+        _ => mm / value,
+    })
+}
+
+// Closed subpaths of a path made of straight segments; those with curves are skipped,
+// following only where each curve ends.
+fn path_loops(d: &str) -> Result<Vec<Vec<Point>>, String> {
+    let mut tokens: Vec<String> = vec![];
+    for c in d.chars() {
+        if c.is_ascii_alphabetic() {
+            tokens.push(c.to_string());
+            tokens.push(String::new());
+        } else if let Some(last) = tokens.last_mut() {
+            last.push(c);
+        }
+    }
+    let mut loops = vec![];
+    let mut current: Vec<Point> = vec![];
+    let mut curved = false;
+    let mut position = Point { x: 0.0, y: 0.0 };
+    for command in tokens.chunks(2) {
+        let values = numbers(command.get(1).map(String::as_str).unwrap_or(""))?;
+        let letter = command[0].chars().next().unwrap_or(' ');
+        let relative = letter.is_ascii_lowercase();
+        let offset = |position: Point| if relative { position } else { Point { x: 0.0, y: 0.0 } };
+        match letter.to_ascii_uppercase() {
+            'M' | 'L' => {
+                for (index, pair) in values.chunks(2).filter(|pair| pair.len() == 2).enumerate() {
+                    let origin = offset(position);
+                    position = Point {
+                        x: origin.x + pair[0],
+                        y: origin.y + pair[1],
+                    };
+                    if letter.eq_ignore_ascii_case(&'M') && index == 0 {
+                        current = vec![];
+                        curved = false;
+                    }
+                    current.push(position);
+                }
+            }
+            'H' => {
+                for x in values {
+                    position.x = offset(position).x + x;
+                    current.push(position);
+                }
+            }
+            'V' => {
+                for y in values {
+                    position.y = offset(position).y + y;
+                    current.push(position);
+                }
+            }
+            'Z' => {
+                if let Some(first) = current.first() {
+                    position = *first;
+                }
+                let points = std::mem::take(&mut current);
+                if !curved {
+                    loops.push(points);
+                }
+            }
+            curve => {
+                // numbers per segment, the end point being the last two
+                let arguments = match curve {
+                    'C' => 6,
+                    'S' | 'Q' => 4,
+                    'T' => 2,
+                    'A' => 7,
+                    other => return Err(format!("unknown SVG path command {}", other)),
+                };
+                for segment in values.chunks(arguments).filter(|segment| segment.len() == arguments) {
+                    let origin = offset(position);
+                    position = Point {
+                        x: origin.x + segment[arguments - 2],
+                        y: origin.y + segment[arguments - 1],
+                    };
+                }
+                curved = true;
+            }
+        }
+    }
+    Ok(loops)
+}
+
+// Shoelace formula.
+fn area(points: &[Point]) -> f64 {
+    let twice: f64 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum();
+    twice.abs() / 2.0
+}
+
+fn overlap(lhs: &[Point], rhs: &[Point]) -> bool {
+    lhs.iter().any(|point| contains(rhs, point))
+        || rhs.iter().any(|point| contains(lhs, point))
+        || edges_cross(lhs, rhs)
+}
+
+fn edges_cross(lhs: &[Point], rhs: &[Point]) -> bool {
+    let edges = |polygon: &[Point]| -> Vec<(Point, Point)> {
+        polygon.iter().cloned().zip(polygon.iter().cloned().cycle().skip(1)).collect()
+    };
+    // sign of the turn from a to b to c
+    let turn = |a: Point, b: Point, c: Point| ((b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)).signum();
+    edges(lhs).iter().any(|&(a, b)| {
+        edges(rhs)
+            .iter()
+            .any(|&(c, d)| turn(a, b, c) * turn(a, b, d) < 0.0 && turn(c, d, a) * turn(c, d, b) < 0.0)
+    })
+}
+
+// Even-odd ray casting.
+fn contains(polygon: &[Point], point: &Point) -> bool {
+    let mut inside = false;
+    for (a, b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+        if (a.y > point.y) != (b.y > point.y) && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+#[test]
+fn dxf_outline_should_convert_units_and_find_obstacles() {
+    let dxf = "0\nSECTION\n2\nHEADER\n9\n$INSUNITS\n70\n6\n0\nENDSEC\n0\nSECTION\n2\nENTITIES\n\
+0\nLWPOLYLINE\n8\n0\n90\n4\n70\n1\n10\n0\n20\n0\n10\n5\n20\n0\n10\n5\n20\n3.5\n10\n0\n20\n3.5\n\
+0\nLWPOLYLINE\n8\n0\n90\n4\n70\n1\n10\n1\n20\n1\n10\n1.2\n20\n1\n10\n1.2\n20\n1.2\n10\n1\n20\n1.2\n\
+0\nENDSEC\n0\nEOF\n";
+
+    let outline = Outline::from_dxf(dxf).unwrap();
+
+    assert_eq!(Point { x: 5000.0, y: 3500.0 }, outline.bounding_box().1);
+    assert_eq!(1, outline.obstacles.len());
+    let deck = outline.to_deck(140).unwrap();
+    assert_eq!((5000, 25), (deck.length, deck.width));
+}
+
+#[test]
+fn svg_outline_should_read_paths_in_millimetres() {
+    let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="600cm" height="400cm" viewBox="0 0 600 400">
+  <path stroke-width="2" d="M 0 0 H 600 V 400 h -600 Z" />
+  <rect x="100" y="100" width="10" height="10" />
+</svg>"#;
+
+    let outline = Outline::from_svg(svg).unwrap();
+
+    assert_eq!(Point { x: 6000.0, y: 4000.0 }, outline.bounding_box().1);
+    assert_eq!(1, outline.obstacles.len());
+    let deck = outline.to_deck(140).unwrap();
+    assert_eq!((6000, 29), (deck.length, deck.width));
+}
+
+#[test]
+fn svg_path_should_skip_curved_subpaths() {
+    let svg = r#"<svg width="100mm" height="100mm" viewBox="0 0 100 100">
+  <path d="M 0 0 H 100 V 100 H 0 Z m 10 10 c 0 5 5 5 5 0 Z m 10 10 h 10 v 10 h -10 z" />
+</svg>"#;
+
+    let outline = Outline::from_svg(svg).unwrap();
+
+    assert_eq!(
+        vec![vec![
+            Point { x: 20.0, y: 20.0 },
+            Point { x: 30.0, y: 20.0 },
+            Point { x: 30.0, y: 30.0 },
+            Point { x: 20.0, y: 30.0 }
+        ]],
+        outline.obstacles
+    );
+}
+
+#[test]
+fn planks_crossing_an_obstacle_or_the_boundary_should_be_cut() {
+    use crate::calepinage::{Calepinage, Line, Plank};
+    use crate::plank_line;
+
+    let svg = r#"<svg width="3000mm" height="280mm" viewBox="0 0 3000 280">
+  <polygon points="0,0 3000,0 3000,140 2000,140 2000,280 0,280" />
+  <rect x="1000" y="150" width="100" height="100" />
+</svg>"#;
+    let outline = Outline::from_svg(svg).unwrap();
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(3000).unwrap()])
+        .with_line(plank_line![
+            Plank::new(500).unwrap(),
+            Plank::new(1000).unwrap(),
+            Plank::new(1500).unwrap()
+        ]);
+    let geometry = Geometry::new(&calepinage, 140);
+
+    let indices: Vec<usize> = outline.planks_to_cut(&geometry).iter().map(|plank| plank.index).collect();
+
+    assert_eq!(vec![2, 3], indices);
+}
@@ -0,0 +1,232 @@
+use std::time::Duration;
+
+use crate::beam::calepine_with_beam_and_control;
+use crate::calepinage::{calepine_with_control, Calepinage, Deck, PlankHeap};
+use crate::control::Control;
+#[cfg(feature = "ilp")]
+use crate::ilp::{calepine_with_cuts_and_control, Objective};
+use crate::optimise::improve_stagger;
+use crate::random::{calepine_randomly_with_control, Random};
+use crate::rules::Rules;
+use crate::score::Score;
+
+const BEAM_WIDTH: usize = 8;
+// Random layouts in a row leaving the front as it is before the search stops.
+const STALE_LAYOUTS: usize = 100;
+
+/// A criterion of a `Score` a client can trade against others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Criterion {
+    Waste,
+    Cuts,
+    Stagger,
+    ShortPieces,
+    Repetition,
+}
+
+impl Criterion {
+    // lower is better for every criterion
+    fn cost(&self, score: &Score) -> f64 {
+        match self {
+            Criterion::Waste => score.waste as f64,
+            Criterion::Cuts => score.cuts as f64,
+            Criterion::Stagger => -(score.min_stagger as f64),
+            Criterion::ShortPieces => score.short_pieces as f64,
+            Criterion::Repetition => score.repetition,
+        }
+    }
+}
+
+/// A layout of the front, with the metric values it reaches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alternative {
+    pub calepinage: Calepinage,
+    pub score: Score,
+}
+
+/// Layouts following the rules none of which is matched or beaten on every criterion by
+/// another one found, searched for at most `budget`: the greedy and beam layouts first, then with
+/// the `ilp` feature the cutting plan of least waste (whose solve runs to its end once started),
+/// then random layouts until a hundred in a row leave the front as it is.
+/// Pieces shorter than `short_length` count as short. Two or three criteria are expected.
+pub fn pareto_front(
+    plank_heap: &PlankHeap,
+    deck: &Deck,
+    rules: &Rules,
+    criteria: &[Criterion],
+    short_length: usize,
+    budget: Duration,
+    seed: u64,
+) -> Result<Vec<Alternative>, String> {
+    let mut control = Control::default().with_budget(budget);
+    pareto_front_with_control(plank_heap, deck, rules, criteria, short_length, seed, &mut control)
+}
+
+/// Like `pareto_front`, searching until the random layouts stop changing the front or `control`
+/// is interrupted, and reporting progress each time a layout joins the front.
+pub fn pareto_front_with_control(
+    plank_heap: &PlankHeap,
+    deck: &Deck,
+    rules: &Rules,
+    criteria: &[Criterion],
+    short_length: usize,
+    seed: u64,
+    control: &mut Control,
+) -> Result<Vec<Alternative>, String> {
+    if criteria.len() < 2 || criteria.len() > 3 {
+        return Err(format!("expected 2 or 3 criteria, got {}", criteria.len()));
+    }
+    let limits = control.limits();
+    let mut front: Vec<Alternative> = vec![];
+    // true when the layout or its improved stagger joins the front
+    let mut offer = |calepinage: Calepinage, control: &mut Control| {
+        let mut candidates = vec![calepinage.clone()];
+        if !control.is_interrupted() {
+            candidates.push(improve_stagger(calepinage, rules));
+        }
+        let mut joined = false;
+        for calepinage in candidates.iter() {
+            let score = Score::new(calepinage, plank_heap, short_length);
+            if add_to_front(&mut front, calepinage, score, criteria) {
+                control.report_layout(calepinage, plank_heap);
+                joined = true;
+            }
+        }
+        joined
+    };
+
+    let strategies = [
+        calepine_with_control(plank_heap.clone(), deck.clone(), rules, &mut limits.limits()),
+        calepine_with_beam_and_control(plank_heap.clone(), deck.clone(), rules, BEAM_WIDTH, &mut limits.limits()),
+    ];
+    for outcome in strategies.iter().flatten().filter(|outcome| !outcome.interrupted) {
+        offer(outcome.calepinage.clone(), control);
+    }
+    #[cfg(feature = "ilp")]
+    {
+        let plan = calepine_with_cuts_and_control(
+            plank_heap.clone(),
+            deck.clone(),
+            rules,
+            &Objective::Waste,
+            &mut limits.limits(),
+        );
+        if let Ok(Some(plan)) = plan {
+            offer(plan.calepinage, control);
+        }
+    }
+    let mut seeds = Random::new(seed);
+    let mut stale = 0;
+    while stale < STALE_LAYOUTS && !control.is_interrupted() {
+        let outcome = calepine_randomly_with_control(
+            plank_heap.clone(),
+            deck.clone(),
+            rules,
+            seeds.next_u64(),
+            &mut limits.limits(),
+        );
+        let joined = match outcome.ok().filter(|outcome| !outcome.interrupted) {
+            Some(outcome) => offer(outcome.calepinage, control),
+            None => false,
+        };
+        stale = if joined { 0 } else { stale + 1 };
+    }
+    Ok(front)
+}
+
+// A candidate joins the front unless a known layout costs as much or less on every criterion,
+// so layouts with the same costs are offered once. True when it joins.
+fn add_to_front(front: &mut Vec<Alternative>, calepinage: &Calepinage, score: Score, criteria: &[Criterion]) -> bool {
+    let costs = |score: &Score| criteria.iter().map(|criterion| criterion.cost(score)).collect::<Vec<f64>>();
+    let candidate = costs(&score);
+    let no_worse = |lhs: &[f64], rhs: &[f64]| lhs.iter().zip(rhs).all(|(l, r)| l <= r);
+    if front.iter().any(|known| no_worse(&costs(&known.score), &candidate)) {
+        return false;
+    }
+    front.retain(|known| !no_worse(&candidate, &costs(&known.score)));
+    front.push(Alternative {
+        calepinage: calepinage.clone(),
+        score,
+    });
+    true
+}
+
+#[test]
+fn pareto_front_should_keep_non_dominated_layouts() {
+    let deck = Deck::new(10, 4).unwrap();
+    let plank_heap = PlankHeap::default().add(6, 3).add(6, 4).add(6, 5).add(2, 1);
+    let criteria = [Criterion::Waste, Criterion::Stagger, Criterion::ShortPieces];
+    let mut control = Control::default();
+
+    let front = pareto_front_with_control(&plank_heap, &deck, &Rules::default(), &criteria, 4, 1, &mut control)
+        .unwrap();
+
+    assert!(!front.is_empty());
+    for alternative in &front {
+        assert!(Rules::default().accept(&alternative.calepinage));
+        let costs: Vec<f64> = criteria.iter().map(|criterion| criterion.cost(&alternative.score)).collect();
+        assert!(front.iter().all(|other| {
+            let other_costs: Vec<f64> = criteria.iter().map(|criterion| criterion.cost(&other.score)).collect();
+            other_costs == costs || other_costs.iter().zip(&costs).any(|(other, cost)| other > cost)
+        }));
+    }
+}
+
+#[test]
+fn pareto_front_should_expect_two_or_three_criteria() {
+    let deck = Deck::new(2, 1).unwrap();
+    let plank_heap = PlankHeap::default().add(1, 2);
+
+    let result = pareto_front(&plank_heap, &deck, &Rules::default(), &[Criterion::Waste], 0, Duration::from_millis(1), 1);
+
+    assert_eq!(Err("expected 2 or 3 criteria, got 1".to_string()), result);
+}
+
+#[test]
+fn pareto_front_should_offer_layouts_of_same_costs_once() {
+    let deck = Deck::new(10, 4).unwrap();
+    let plank_heap = PlankHeap::default().add(6, 3).add(6, 4).add(6, 5).add(2, 1);
+    let criteria = [Criterion::Waste, Criterion::Cuts];
+    let mut control = Control::default();
+
+    let front = pareto_front_with_control(&plank_heap, &deck, &Rules::default(), &criteria, 0, 1, &mut control)
+        .unwrap();
+
+    // whole planks waste nothing and need no cut, which no other layout beats
+    assert_eq!(1, front.len());
+    assert_eq!((0, 0), (front[0].score.waste, front[0].score.cuts));
+}
+
+#[test]
+fn pareto_front_should_stop_when_cancelled() {
+    use crate::control::{CancellationToken, Progress};
+
+    let deck = Deck::new(10, 4).unwrap();
+    let plank_heap = PlankHeap::default().add(6, 3).add(6, 4).add(6, 5).add(2, 1);
+    let token = CancellationToken::new();
+    let cancelling = token.clone();
+    let mut control = Control::default()
+        .with_cancellation(token)
+        .with_progress(move |_: &Progress| cancelling.cancel());
+    let criteria = [Criterion::Stagger, Criterion::Repetition];
+
+    let front = pareto_front_with_control(&plank_heap, &deck, &Rules::default(), &criteria, 0, 1, &mut control)
+        .unwrap();
+
+    assert!(!front.is_empty());
+}
+
+#[cfg(feature = "ilp")]
+#[test]
+fn pareto_front_should_offer_cutting_plans() {
+    let deck = Deck::new(6, 2).unwrap();
+    let plank_heap = PlankHeap::default().add(1, 10).add(1, 4).add(1, 2);
+    let criteria = [Criterion::Waste, Criterion::Cuts];
+    let mut control = Control::default();
+
+    let front = pareto_front_with_control(&plank_heap, &deck, &Rules::default(), &criteria, 0, 1, &mut control)
+        .unwrap();
+
+    assert_eq!(1, front.len());
+    assert_eq!((4, 1), (front[0].score.waste, front[0].score.cuts));
+}
@@ -0,0 +1,328 @@
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+
+use crate::calepinage::{Calepinage, Deck, Plank, PlankHeap};
+use crate::geometry::{Geometry, PlacedPlank};
+use crate::score::{pieces_by_stock, Score, Weights};
+
+// A4 landscape, in points
+const PAGE_WIDTH: f32 = 842.0;
+const PAGE_HEIGHT: f32 = 595.0;
+const MARGIN: f32 = 40.0;
+const POINTS_PER_MM: f32 = 72.0 / 25.4;
+const SCALES: [usize; 9] = [5, 10, 20, 25, 50, 100, 200, 500, 1000];
+const CUT_LIST_ROWS: usize = 40;
+const FONT: Name = Name(b"F1");
+
+/// Options of the plan set: lengths in millimetres. Decks longer than `section_length`
+/// get a close-up page per section. The cover prints the score of the layout under `weights`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanSet {
+    pub title: String,
+    pub plank_width: usize,
+    pub section_length: usize,
+    pub weights: Weights,
+}
+
+impl Default for PlanSet {
+    fn default() -> Self {
+        PlanSet {
+            title: "Calepinage".to_string(),
+            plank_width: 140,
+            section_length: 3000,
+            weights: Weights::default(),
+        }
+    }
+}
+
+/// PDF of a cover with the deck and material summary, the material list going on over
+/// more pages when long, the layout drawing at scale, the cut list and, for long decks,
+/// close-ups of each section.
+pub fn plan_set_pdf(deck: &Deck, plank_heap: &PlankHeap, calepinage: &Calepinage, options: &PlanSet) -> Vec<u8> {
+    let geometry = Geometry::new(calepinage, options.plank_width);
+    let mut pages = cover(deck, plank_heap, calepinage, options);
+    pages.push(drawing(
+        &geometry,
+        0,
+        deck.length,
+        deck.width * options.plank_width,
+        &format!("{}: layout", options.title),
+    ));
+    pages.extend(cut_list(&geometry));
+    if deck.length > options.section_length && options.section_length > 0 {
+        let sections = deck.length.div_ceil(options.section_length);
+        for section in 0..sections {
+            let start = section * options.section_length;
+            let end = (start + options.section_length).min(deck.length);
+            pages.push(drawing(
+                &geometry,
+                start,
+                end,
+                deck.width * options.plank_width,
+                &format!("Section {} of {}: {} mm to {} mm", section + 1, sections, start, end),
+            ));
+        }
+    }
+    assemble(pages)
+}
+
+fn assemble(pages: Vec<Content>) -> Vec<u8> {
+    let mut pdf = Pdf::new();
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let font_id = Ref::new(3);
+    let page_ids: Vec<Ref> = (0..pages.len()).map(|index| Ref::new(4 + 2 * index as i32)).collect();
+
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id)
+        .kids(page_ids.iter().copied())
+        .count(pages.len() as i32);
+    pdf.type1_font(font_id).base_font(Name(b"Helvetica"));
+    for (page_id, content) in page_ids.into_iter().zip(pages) {
+        let content_id = Ref::new(page_id.get() + 1);
+        let mut page = pdf.page(page_id);
+        page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT));
+        page.parent(page_tree_id);
+        page.contents(content_id);
+        page.resources().fonts().pair(FONT, font_id);
+        page.finish();
+        pdf.stream(content_id, &content.finish());
+    }
+    pdf.finish()
+}
+
+// The cover, then pages of the rest of the material list when it does not fit.
+fn cover(deck: &Deck, plank_heap: &PlankHeap, calepinage: &Calepinage, options: &PlanSet) -> Vec<Content> {
+    let mut content = Content::new();
+    let mut y = PAGE_HEIGHT - MARGIN - 24.0;
+    text(&mut content, MARGIN, y, 24.0, &options.title);
+    y -= 40.0;
+    let deck_width = deck.width * options.plank_width;
+    let laid: Vec<usize> = calepinage.0.iter().flat_map(|line| line.lengths()).collect();
+    let summary = [
+        format!("Deck: {} mm x {} mm, {} lines of {} mm", deck.length, deck_width, deck.width, options.plank_width),
+        format!("Area: {:.2} m2", (deck.length * deck_width) as f64 / 1_000_000.0),
+        format!("Planks laid: {}, {} mm in total", laid.len(), laid.iter().sum::<usize>()),
+        format!("Planks in stock: {}, {} mm in total", plank_heap.planks().len(), plank_heap.total_length()),
+        format!(
+            "Score: {:.2} ({})",
+            options.weights.evaluate(calepinage, plank_heap),
+            Score::new(calepinage, plank_heap, options.weights.short_length)
+        ),
+    ];
+    for line in summary.iter() {
+        text(&mut content, MARGIN, y, 12.0, line);
+        y -= 18.0;
+    }
+
+    y -= 12.0;
+    text(&mut content, MARGIN, y, 14.0, "Material");
+    y -= 20.0;
+    let pieces: Vec<&Plank> = calepinage.0.iter().flat_map(|line| line.0.iter()).collect();
+    let cut = pieces_by_stock(plank_heap, &pieces);
+    let mut lengths: Vec<usize> = plank_heap.planks().iter().map(|plank| plank.length).collect();
+    lengths.sort_unstable_by(|lhs, rhs| rhs.cmp(lhs));
+    lengths.dedup();
+    let mut pages = vec![];
+    for length in lengths {
+        if y < MARGIN {
+            pages.push(content);
+            content = Content::new();
+            y = PAGE_HEIGHT - MARGIN - 14.0;
+            text(&mut content, MARGIN, y, 14.0, "Material (continued)");
+            y -= 20.0;
+        }
+        let stock: Vec<&Vec<&Plank>> = plank_heap
+            .planks()
+            .iter()
+            .zip(&cut)
+            .filter(|(plank, _)| plank.length == length)
+            .map(|(_, pieces)| pieces)
+            .collect();
+        let used = stock.iter().filter(|pieces| !pieces.is_empty()).count();
+        text(
+            &mut content,
+            MARGIN,
+            y,
+            11.0,
+            &format!("{} mm: {} in stock, {} laid, {} left", length, stock.len(), used, stock.len() - used),
+        );
+        y -= 15.0;
+    }
+    pages.push(content);
+    pages
+}
+
+// Smallest standard scale at which the area fits the page, 1:`scale`.
+fn scale_for(length: usize, width: usize) -> usize {
+    let available_width = (PAGE_WIDTH - 2.0 * MARGIN) / POINTS_PER_MM;
+    let available_height = (PAGE_HEIGHT - 2.0 * MARGIN - 40.0) / POINTS_PER_MM;
+    SCALES
+        .iter()
+        .copied()
+        .find(|&scale| length as f32 / scale as f32 <= available_width && width as f32 / scale as f32 <= available_height)
+        .unwrap_or_else(|| {
+            let fitting = (length as f32 / available_width).max(width as f32 / available_height);
+            fitting.ceil() as usize
+        })
+}
+
+// Planks between x = `start` and x = `end`, the first line at the top.
+fn drawing(geometry: &Geometry, start: usize, end: usize, deck_width: usize, title: &str) -> Content {
+    let scale = scale_for(end - start, deck_width);
+    let points = |mm: usize| mm as f32 / scale as f32 * POINTS_PER_MM;
+    let top = PAGE_HEIGHT - MARGIN - 40.0;
+
+    let mut content = Content::new();
+    text(&mut content, MARGIN, PAGE_HEIGHT - MARGIN - 14.0, 14.0, title);
+    text(&mut content, MARGIN, PAGE_HEIGHT - MARGIN - 30.0, 10.0, &format!("Scale 1:{}", scale));
+    content.set_line_width(0.5);
+    let visible = geometry
+        .planks
+        .iter()
+        .filter(|plank| plank.x_start < end && plank.x_end > start);
+    for plank in visible {
+        let x_start = plank.x_start.max(start);
+        let x_end = plank.x_end.min(end);
+        let x = MARGIN + points(x_start - start);
+        let y = top - points(plank.y + plank.width);
+        content.rect(x, y, points(x_end - x_start), points(plank.width));
+        content.stroke();
+        if points(x_end - x_start) > 40.0 && points(plank.width) > 8.0 {
+            text(&mut content, x + 2.0, y + 2.0, 6.0, &plank_name(plank));
+        }
+    }
+    content
+}
+
+fn cut_list(geometry: &Geometry) -> Vec<Content> {
+    geometry
+        .planks
+        .chunks(CUT_LIST_ROWS)
+        .enumerate()
+        .map(|(page, planks)| {
+            let mut content = Content::new();
+            let mut y = PAGE_HEIGHT - MARGIN - 14.0;
+            text(&mut content, MARGIN, y, 14.0, &format!("Cut list ({})", page + 1));
+            y -= 24.0;
+            let columns = [MARGIN, MARGIN + 60.0, MARGIN + 130.0, MARGIN + 300.0];
+            for (x, header) in columns.iter().zip(["Line", "Position", "Plank", "Length (mm)"].iter()) {
+                text(&mut content, *x, y, 10.0, header);
+            }
+            for plank in planks {
+                y -= 12.0;
+                let position = geometry.line(plank.line).take_while(|other| other.index != plank.index).count();
+                let row = [
+                    (plank.line + 1).to_string(),
+                    (position + 1).to_string(),
+                    plank_name(plank),
+                    plank.plank.length.to_string(),
+                ];
+                for (x, cell) in columns.iter().zip(row.iter()) {
+                    text(&mut content, *x, y, 10.0, cell);
+                }
+            }
+            content
+        })
+        .collect()
+}
+
+fn plank_name(plank: &PlacedPlank) -> String {
+    plank.plank.id.clone().unwrap_or_else(|| format!("#{}", plank.index + 1))
+}
+
+// Helvetica has no glyph for other characters in its standard encoding.
+fn text(content: &mut Content, x: f32, y: f32, size: f32, value: &str) {
+    let ascii: Vec<u8> = value
+        .chars()
+        .map(|c| if c.is_ascii() && !c.is_ascii_control() { c as u8 } else { b'?' })
+        .collect();
+    content.begin_text();
+    content.set_font(FONT, size);
+    content.next_line(x, y);
+    content.show(Str(&ascii));
+    content.end_text();
+}
+
+#[cfg(test)]
+use crate::calepinage::{calepine, Line};
+#[cfg(test)]
+use crate::plank_line;
+
+// Text shown and number of rectangles drawn on each page, in order.
+#[cfg(test)]
+fn pages_of(pdf: &[u8]) -> Vec<(Vec<String>, usize)> {
+    let document = String::from_utf8_lossy(pdf);
+    document
+        .split(">>\nstream\n")
+        .skip(1)
+        .map(|stream| {
+            let operations = stream[..stream.find("\nendstream").unwrap_or(stream.len())].lines();
+            let shown = operations
+                .clone()
+                .filter_map(|operation| operation.strip_suffix(" Tj"))
+                .map(|shown| shown[1..shown.len() - 1].replace("\\", ""))
+                .collect();
+            (shown, operations.filter(|operation| operation.ends_with(" re")).count())
+        })
+        .collect()
+}
+
+#[test]
+fn plan_set_should_have_cover_drawing_cut_list_and_sections() {
+    let deck = Deck::new(7000, 3).unwrap();
+    let plank_heap = PlankHeap::default().add(3, 4000).add(3, 3000);
+
+    let calepinage = calepine(plank_heap.clone(), deck.clone()).unwrap();
+    let pdf = plan_set_pdf(&deck, &plank_heap, &calepinage, &PlanSet::default());
+
+    assert!(pdf.starts_with(b"%PDF-"));
+    // cover, drawing, one page of cut list, three sections
+    let document = String::from_utf8_lossy(&pdf);
+    assert!(document.contains("/Count 6"));
+    let pages = pages_of(&pdf);
+    assert_eq!(6, pages.len());
+    let (cover, _) = &pages[0];
+    assert_eq!("Calepinage", cover[0]);
+    assert!(cover.contains(&"Deck: 7000 mm x 420 mm, 3 lines of 140 mm".to_string()));
+    assert!(cover.contains(&"Planks laid: 6, 21000 mm in total".to_string()));
+    assert!(cover.iter().any(|shown| shown.starts_with("Score: ")));
+    assert!(cover.contains(&"4000 mm: 3 in stock, 3 laid, 0 left".to_string()));
+    assert_eq!((vec!["Calepinage: layout".to_string(), "Scale 1:50".to_string()], 6), pages[1]);
+    let (cut_list, _) = &pages[2];
+    assert_eq!("Cut list (1)", cut_list[0]);
+    // a row of line, position, name and length per plank after the headers
+    assert_eq!(5 + 6 * 4, cut_list.len());
+    assert_eq!(vec!["2", "2", "#4", "4000"], cut_list[5 + 3 * 4..5 + 4 * 4].to_vec());
+    let sections: Vec<(&str, usize)> = pages[3..].iter().map(|(shown, planks)| (shown[0].as_str(), *planks)).collect();
+    assert_eq!(
+        vec![
+            ("Section 1 of 3: 0 mm to 3000 mm", 3),
+            ("Section 2 of 3: 3000 mm to 6000 mm", 5),
+            ("Section 3 of 3: 6000 mm to 7000 mm", 3)
+        ],
+        sections
+    );
+}
+
+#[test]
+fn material_list_should_count_cut_planks_and_go_on_over_pages() {
+    let deck = Deck::new(3000, 1).unwrap();
+    let plank_heap = (1..=40).fold(PlankHeap::default(), |heap, length| heap.add(1, length)).add(1, 3000);
+    let calepinage = Calepinage::default().with_line(plank_line![Plank::new(2000).unwrap(), Plank::new(1000).unwrap()]);
+
+    let pdf = plan_set_pdf(&deck, &plank_heap, &calepinage, &PlanSet::default());
+
+    let pages = pages_of(&pdf);
+    let (cover, _) = &pages[0];
+    assert!(cover.contains(&"3000 mm: 1 in stock, 1 laid, 0 left".to_string()));
+    let (continued, _) = &pages[1];
+    assert_eq!("Material (continued)", continued[0]);
+    assert_eq!(Some(&"1 mm: 1 in stock, 0 laid, 1 left".to_string()), continued.last());
+    assert_eq!("Calepinage: layout", pages[2].0[0]);
+}
+
+#[test]
+fn scale_should_be_the_smallest_standard_one_fitting_the_page() {
+    assert_eq!(20, scale_for(5000, 1000));
+    assert_eq!(100, scale_for(20000, 4000));
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+
+use crate::calepinage::{gcd, Deck, PlankHeap};
+
+/// Why a heap cannot cover a deck whatever the strategy. Planks longer than the line are not usable,
+/// nor, with joists, planks that can neither end on a joist nor end a line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Infeasibility {
+    NotEnoughLength { available: usize, needed: usize },
+    NotEnoughPlanks { available: usize, needed: usize },
+    LengthNotReachable { gcd: usize, deck_length: usize },
+    LineCannotBeFilled { deck_length: usize },
+    NotEnoughLineEnds { available: usize, needed: usize, joist_spacing: usize },
+}
+
+impl fmt::Display for Infeasibility {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Infeasibility::NotEnoughLength { available, needed } => write!(
+                f,
+                "usable planks add up to {}, the deck needs {}",
+                available, needed
+            ),
+            Infeasibility::NotEnoughPlanks { available, needed } => write!(
+                f,
+                "{} planks fit on the joists, the deck has {} lines",
+                available, needed
+            ),
+            Infeasibility::LengthNotReachable { gcd, deck_length } => write!(
+                f,
+                "every plank length is a multiple of {}, the line length {} is not",
+                gcd, deck_length
+            ),
+            Infeasibility::LineCannotBeFilled { deck_length } => {
+                write!(f, "no set of planks adds up to the line length {}", deck_length)
+            }
+            Infeasibility::NotEnoughLineEnds {
+                available,
+                needed,
+                joist_spacing,
+            } => write!(
+                f,
+                "with junctions on joists every {}, each line ends with a plank of another length: {} such planks for {} lines",
+                joist_spacing, available, needed
+            ),
+        }
+    }
+}
+
+/// Instant bounds telling when `plank_heap` cannot cover `deck`, whatever the rules:
+/// `Ok` means the deck may be feasible, not that it is.
+/// With `joist_spacing`, junctions must fall on joists at every multiple of it from the start of the lines.
+pub fn precheck(plank_heap: &PlankHeap, deck: &Deck, joist_spacing: Option<usize>) -> Result<(), Infeasibility> {
+    let mut usable: Vec<usize> = plank_heap
+        .planks()
+        .iter()
+        .map(|plank| plank.length)
+        .filter(|&length| length > 0 && length <= deck.length)
+        .collect();
+    if deck.width == 0 || deck.length == 0 {
+        return Ok(());
+    }
+
+    let needed = deck.length * deck.width;
+    let available: usize = usable.iter().sum();
+    if available < needed {
+        return Err(Infeasibility::NotEnoughLength { available, needed });
+    }
+
+    let gcd = usable.iter().fold(0, |unit, &length| gcd(unit, length));
+    if !deck.length.is_multiple_of(gcd) {
+        return Err(Infeasibility::LengthNotReachable {
+            gcd,
+            deck_length: deck.length,
+        });
+    }
+
+    if let Some(spacing) = joist_spacing.filter(|&spacing| spacing > 0) {
+        // a plank ends on a joist unless it ends the line
+        let end_remainder = deck.length % spacing;
+        usable.retain(|length| length % spacing == 0 || length % spacing == end_remainder);
+        if usable.len() < deck.width {
+            return Err(Infeasibility::NotEnoughPlanks {
+                available: usable.len(),
+                needed: deck.width,
+            });
+        }
+        if end_remainder != 0 {
+            let line_ends = usable.iter().filter(|&&length| length % spacing == end_remainder).count();
+            if line_ends < deck.width {
+                return Err(Infeasibility::NotEnoughLineEnds {
+                    available: line_ends,
+                    needed: deck.width,
+                    joist_spacing: spacing,
+                });
+            }
+        }
+        let available: usize = usable.iter().sum();
+        if available < needed {
+            return Err(Infeasibility::NotEnoughLength { available, needed });
+        }
+    }
+
+    if !is_subset_sum(&usable, deck.length, gcd) {
+        return Err(Infeasibility::LineCannotBeFilled {
+            deck_length: deck.length,
+        });
+    }
+    Ok(())
+}
+
+// Whether some of the lengths add up to `target`, counting in `unit`, which divides them all.
+fn is_subset_sum(lengths: &[usize], target: usize, unit: usize) -> bool {
+    let target = target / unit;
+    let mut reachable = vec![false; target + 1];
+    reachable[0] = true;
+    for length in lengths.iter().map(|length| length / unit) {
+        for sum in (length..=target).rev() {
+            reachable[sum] = reachable[sum] || reachable[sum - length];
+        }
+        if reachable[target] {
+            return true;
+        }
+    }
+    reachable[target]
+}
+
+#[test]
+fn precheck_should_compare_total_length_with_deck_area() {
+    let deck = Deck::new(10, 3).unwrap();
+    let plank_heap = PlankHeap::default().add(2, 10).add(1, 12).add(1, 5);
+
+    assert_eq!(
+        "usable planks add up to 25, the deck needs 30",
+        precheck(&plank_heap, &deck, None).unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn precheck_should_find_unreachable_line_length() {
+    let deck = Deck::new(7, 1).unwrap();
+    let plank_heap = PlankHeap::default().add(4, 2).add(2, 4);
+
+    assert_eq!(
+        Err(Infeasibility::LengthNotReachable { gcd: 2, deck_length: 7 }),
+        precheck(&plank_heap, &deck, None)
+    );
+}
+
+#[test]
+fn precheck_should_find_line_no_planks_fill() {
+    let deck = Deck::new(7, 1).unwrap();
+    let plank_heap = PlankHeap::default().add(3, 5).add(1, 3);
+
+    assert_eq!(
+        Err(Infeasibility::LineCannotBeFilled { deck_length: 7 }),
+        precheck(&plank_heap, &deck, None)
+    );
+}
+
+#[test]
+fn precheck_should_need_a_line_end_per_line_between_joists() {
+    let deck = Deck::new(10, 2).unwrap();
+    let plank_heap = PlankHeap::default().add(5, 4).add(1, 2);
+
+    assert_eq!(Ok(()), precheck(&plank_heap, &deck, None));
+    assert_eq!(
+        Err(Infeasibility::NotEnoughLineEnds {
+            available: 1,
+            needed: 2,
+            joist_spacing: 4
+        }),
+        precheck(&plank_heap, &deck, Some(4))
+    );
+}
@@ -0,0 +1,203 @@
+use crate::calepinage::{
+    select_planks_for_line, Calepinage, CalepinageError, Deck, Line, PlankHeap,
+};
+use crate::control::{Control, Outcome};
+use crate::rules::Rules;
+
+/// Seeded generator (splitmix64): a seed gives the same layout on every platform and release,
+/// so a layout chosen by a client can be rebuilt from its seed.
+#[derive(Debug, Clone)]
+pub struct Random(u64);
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Random(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..bound`, `bound` being greater than 0.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// A number in `0.0..1.0`.
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for index in (1..items.len()).rev() {
+            items.swap(index, self.below(index + 1));
+        }
+    }
+}
+
+const ATTEMPTS_PER_LINE: usize = 20;
+
+/// Like `calepine_with_rules`, picking planks in a random order on each line
+/// instead of the longest first.
+pub fn calepine_randomly(
+    plank_heap: PlankHeap,
+    deck: Deck,
+    rules: &Rules,
+    seed: u64,
+) -> Result<Calepinage, CalepinageError> {
+    calepine_randomly_with_control(plank_heap, deck, rules, seed, &mut Control::default())
+        .map(|outcome| outcome.calepinage)
+}
+
+/// Like `calepine_randomly`, checking `control` and reporting progress after each line.
+pub fn calepine_randomly_with_control(
+    plank_heap: PlankHeap,
+    deck: Deck,
+    rules: &Rules,
+    seed: u64,
+    control: &mut Control,
+) -> Result<Outcome, CalepinageError> {
+    let mut random = Random::new(seed);
+    let mut the_plank_heap = plank_heap.clone();
+    let mut calepinage = Calepinage::default();
+    for _ in 0..deck.width {
+        if control.is_interrupted() {
+            return Ok(Outcome {
+                calepinage,
+                interrupted: true,
+            });
+        }
+        let is_forbidden = rules.forbidden_junctions(&calepinage);
+        let mut attempt = Err(CalepinageError::NotEnoughPlanks);
+        for _ in 0..ATTEMPTS_PER_LINE {
+            let mut planks = the_plank_heap.planks().to_vec();
+            random.shuffle(&mut planks);
+            attempt = select_planks_for_line(&PlankHeap::from_planks(planks), deck.length, &is_forbidden)
+                .and_then(|step| match &rules.repetition {
+                    Some(repetition)
+                        if repetition.repeats(&Line(step.selected.planks().to_vec()), &calepinage) =>
+                    {
+                        Err(CalepinageError::RepeatedLine(calepinage.0.len()))
+                    }
+                    _ => Ok(step),
+                });
+            if attempt.is_ok() {
+                break;
+            }
+        }
+        let step = attempt?;
+        the_plank_heap = step.remaining;
+        calepinage = calepinage.with_line(Line(step.selected.planks().to_vec()));
+        control.report_layout(&calepinage, &plank_heap);
+    }
+    Ok(Outcome {
+        calepinage,
+        interrupted: false,
+    })
+}
+
+/// Up to `count` distinct layouts for the client to choose from, each rebuilt by
+/// `calepine_randomly` with the seed given next to it.
+pub fn random_candidates(
+    plank_heap: &PlankHeap,
+    deck: &Deck,
+    rules: &Rules,
+    seed: u64,
+    count: usize,
+) -> Vec<(u64, Calepinage)> {
+    let mut seeds = Random::new(seed);
+    let mut candidates: Vec<(u64, Calepinage)> = vec![];
+    for _ in 0..count * ATTEMPTS_PER_LINE {
+        if candidates.len() == count {
+            break;
+        }
+        let candidate_seed = seeds.next_u64();
+        if let Ok(calepinage) =
+            calepine_randomly(plank_heap.clone(), deck.clone(), rules, candidate_seed)
+        {
+            if candidates.iter().all(|(_, other)| other.lengths() != calepinage.lengths()) {
+                candidates.push((candidate_seed, calepinage));
+            }
+        }
+    }
+    candidates
+}
+
+#[cfg(test)]
+use crate::calepinage::Plank;
+
+#[test]
+fn same_seed_should_give_same_numbers() {
+    let mut random = Random::new(42);
+    let mut same = Random::new(42);
+
+    assert_eq!(
+        (0..5).map(|_| random.next_u64()).collect::<Vec<u64>>(),
+        (0..5).map(|_| same.next_u64()).collect::<Vec<u64>>()
+    );
+}
+
+#[test]
+fn shuffle_should_keep_items() {
+    let mut items = vec![1, 2, 3, 4, 5, 6];
+    Random::new(7).shuffle(&mut items);
+    items.sort();
+
+    assert_eq!(vec![1, 2, 3, 4, 5, 6], items);
+}
+
+#[test]
+fn random_layout_should_not_lack_planks_kept_aside_on_a_failed_line() {
+    let deck = Deck::new(4200, 40).unwrap();
+    let plank_heap = PlankHeap::default().add(80, 2400).add(80, 1800).add(80, 1200).add(80, 600);
+
+    assert!(calepine_randomly(plank_heap.clone(), deck.clone(), &Rules::default(), 1).is_ok());
+    // the first line drawn, 600 600 600 600 1800, leaves a junction under every length
+    assert!(matches!(
+        calepine_randomly(plank_heap, deck, &Rules::default(), 5),
+        Err(CalepinageError::OnlyUnusablePlanksRemaining(_))
+    ));
+}
+
+#[test]
+fn random_layout_should_stop_when_cancelled() {
+    use crate::control::{CancellationToken, Progress};
+
+    let deck = Deck::new(10, 3).unwrap();
+    let plank_heap = PlankHeap::default().add(4, 3).add(4, 4).add(4, 5);
+    let token = CancellationToken::new();
+    let cancelling = token.clone();
+    let mut control = Control::default()
+        .with_cancellation(token)
+        .with_progress(move |progress: &Progress| {
+            if progress.lines_completed == 1 {
+                cancelling.cancel();
+            }
+        });
+
+    let outcome = calepine_randomly_with_control(plank_heap, deck, &Rules::default(), 3, &mut control).unwrap();
+
+    assert!(outcome.interrupted);
+    assert_eq!(1, outcome.calepinage.0.len());
+}
+
+#[test]
+fn random_candidates_should_differ_in_lengths() {
+    let deck = Deck::new(3, 2).unwrap();
+    let plank_heap = ["A", "B"]
+        .iter()
+        .map(|id| Plank::new(2).unwrap().with_id(id))
+        .chain(["C", "D"].iter().map(|id| Plank::new(1).unwrap().with_id(id)))
+        .fold(PlankHeap::default(), PlankHeap::add_plank);
+
+    let candidates = random_candidates(&plank_heap, &deck, &Rules::default(), 1, 6);
+
+    // [2, 1] then [1, 2], or the other way round, whichever planks are picked
+    let mut lengths: Vec<Vec<Vec<usize>>> = candidates.iter().map(|(_, calepinage)| calepinage.lengths()).collect();
+    lengths.sort();
+    assert_eq!(vec![vec![vec![1, 2], vec![2, 1]], vec![vec![2, 1], vec![1, 2]]], lengths);
+}
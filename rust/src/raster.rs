@@ -0,0 +1,229 @@
+use crate::calepinage::{Calepinage, Deck};
+use crate::geometry::{Geometry, PlacedPlank};
+
+// 16 megapixels, 48 MB of RGB.
+const MAX_PIXELS: usize = 1 << 24;
+const BACKGROUND: [u8; 3] = [255, 255, 255];
+const OUTLINE: [u8; 3] = [64, 64, 64];
+const JUNCTION: [u8; 3] = [220, 20, 60];
+const UNKNOWN: [u8; 3] = [190, 190, 190];
+const PALETTE: [[u8; 3]; 8] = [
+    [222, 184, 135],
+    [160, 120, 80],
+    [205, 133, 63],
+    [244, 164, 96],
+    [139, 90, 43],
+    [210, 180, 140],
+    [188, 143, 143],
+    [128, 128, 0],
+];
+
+/// What gives a plank its colour: planks of the same length, or of the same batch, share
+/// one. Planks without a batch are grey.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Colouring {
+    Length,
+    Batch,
+}
+
+/// Options of the preview: the image is `width` pixels wide, its height following the deck,
+/// lines of planks `plank_width` millimetres wide.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preview {
+    pub width: u32,
+    pub plank_width: usize,
+    pub colouring: Colouring,
+    pub highlight_junctions: bool,
+}
+
+impl Default for Preview {
+    fn default() -> Self {
+        Preview {
+            width: 800,
+            plank_width: 140,
+            colouring: Colouring::Length,
+            highlight_junctions: true,
+        }
+    }
+}
+
+/// PNG image of the calepinage, the first line at the top, junctions in red if highlighted.
+/// Images over 16 megapixels are refused.
+pub fn preview_png(deck: &Deck, calepinage: &Calepinage, options: &Preview) -> Result<Vec<u8>, String> {
+    let raster = render(deck, calepinage, options)?;
+    let mut png = vec![];
+    let mut encoder = png::Encoder::new(&mut png, raster.width, raster.height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+    writer.write_image_data(&raster.pixels).map_err(|error| error.to_string())?;
+    writer.finish().map_err(|error| error.to_string())?;
+    Ok(png)
+}
+
+// RGB pixels, row by row from the top.
+struct Raster {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Raster {
+    fn fill(&mut self, x_start: u32, y_start: u32, x_end: u32, y_end: u32, colour: [u8; 3]) {
+        for y in y_start..y_end.min(self.height) {
+            for x in x_start..x_end.min(self.width) {
+                let index = 3 * (y as usize * self.width as usize + x as usize);
+                self.pixels[index..index + 3].copy_from_slice(&colour);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        let index = 3 * (y as usize * self.width as usize + x as usize);
+        [self.pixels[index], self.pixels[index + 1], self.pixels[index + 2]]
+    }
+}
+
+fn render(deck: &Deck, calepinage: &Calepinage, options: &Preview) -> Result<Raster, String> {
+    if options.width == 0 || deck.length == 0 || options.plank_width == 0 {
+        return Err("a preview can't have a zero width or plank width, nor the deck a zero length".to_string());
+    }
+    let geometry = Geometry::new(calepinage, options.plank_width);
+    let scale = options.width as f64 / deck.length as f64;
+    let pixels = |mm: usize| (mm as f64 * scale).round() as u32;
+    let height = deck
+        .width
+        .checked_mul(options.plank_width)
+        .map(|mm| ((mm as f64 * scale).round() as usize).max(1))
+        .filter(|&height| matches!((options.width as usize).checked_mul(height), Some(count) if count <= MAX_PIXELS))
+        .ok_or_else(|| format!("a preview can't have more than {} pixels", MAX_PIXELS))?;
+    let mut raster = Raster {
+        width: options.width,
+        height: height as u32,
+        pixels: BACKGROUND.repeat(options.width as usize * height),
+    };
+
+    let colours = Colours::new(&geometry, options.colouring);
+    for plank in &geometry.planks {
+        let (x_start, x_end) = (pixels(plank.x_start), pixels(plank.x_end));
+        let (y_start, y_end) = (pixels(plank.y), pixels(plank.y + plank.width));
+        raster.fill(x_start, y_start, x_end, y_end, OUTLINE);
+        // a one pixel outline, as long as the plank is more than two pixels wide
+        raster.fill(x_start + 1, y_start + 1, x_end.saturating_sub(1), y_end.saturating_sub(1), colours.of(plank));
+    }
+    if options.highlight_junctions {
+        for (line_index, line) in calepinage.0.iter().enumerate() {
+            let y = line_index * options.plank_width;
+            for junction in line.compute_junction() {
+                let x = pixels(junction.position());
+                raster.fill(x.saturating_sub(1), pixels(y), x + 1, pixels(y + options.plank_width), JUNCTION);
+            }
+        }
+    }
+    Ok(raster)
+}
+
+// Palette colour of each length, longest first, or of each batch, by name.
+struct Colours {
+    colouring: Colouring,
+    keys: Vec<String>,
+}
+
+impl Colours {
+    fn new(geometry: &Geometry, colouring: Colouring) -> Self {
+        let mut planks: Vec<&PlacedPlank> = geometry.planks.iter().collect();
+        planks.sort_by_key(|plank| std::cmp::Reverse(plank.plank.length));
+        let mut keys: Vec<String> = planks.into_iter().filter_map(|plank| key(plank, colouring)).collect();
+        if colouring == Colouring::Batch {
+            keys.sort();
+        }
+        keys.dedup();
+        Colours { colouring, keys }
+    }
+
+    fn of(&self, plank: &PlacedPlank) -> [u8; 3] {
+        key(plank, self.colouring)
+            .and_then(|key| self.keys.iter().position(|other| *other == key))
+            .map_or(UNKNOWN, |index| PALETTE[index % PALETTE.len()])
+    }
+}
+
+fn key(plank: &PlacedPlank, colouring: Colouring) -> Option<String> {
+    match colouring {
+        Colouring::Length => Some(plank.plank.length.to_string()),
+        Colouring::Batch => plank.plank.metadata.batch.clone(),
+    }
+}
+
+#[cfg(test)]
+use crate::calepinage::{Line, Plank};
+#[cfg(test)]
+use crate::plank_line;
+
+#[test]
+fn preview_should_colour_planks_and_highlight_junctions() {
+    let deck = Deck::new(3000, 2).unwrap();
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(2000).unwrap(), Plank::new(1000).unwrap()])
+        .with_line(plank_line![Plank::new(1000).unwrap(), Plank::new(2000).unwrap()]);
+    let options = Preview {
+        width: 300,
+        ..Preview::default()
+    };
+
+    let raster = render(&deck, &calepinage, &options).unwrap();
+
+    assert_eq!((300, 28), (raster.width, raster.height));
+    // longest planks take the first colour
+    assert_eq!(PALETTE[0], raster.pixel(50, 7));
+    assert_eq!(PALETTE[1], raster.pixel(50, 21));
+    assert_eq!(JUNCTION, raster.pixel(200, 7));
+    assert_eq!(JUNCTION, raster.pixel(100, 21));
+    assert_eq!(OUTLINE, raster.pixel(0, 0));
+}
+
+#[test]
+fn preview_should_colour_planks_by_batch() {
+    let deck = Deck::new(3000, 1).unwrap();
+    let calepinage = Calepinage::default().with_line(plank_line![
+        Plank::new(2000).unwrap().with_batch("B"),
+        Plank::new(1000).unwrap()
+    ]);
+    let options = Preview {
+        width: 300,
+        colouring: Colouring::Batch,
+        highlight_junctions: false,
+        ..Preview::default()
+    };
+
+    let raster = render(&deck, &calepinage, &options).unwrap();
+
+    assert_eq!(PALETTE[0], raster.pixel(50, 7));
+    assert_eq!(UNKNOWN, raster.pixel(250, 7));
+    assert_eq!(OUTLINE, raster.pixel(200, 7));
+}
+
+#[test]
+fn preview_png_should_start_with_the_png_signature() {
+    let deck = Deck::new(3000, 1).unwrap();
+    let calepinage = Calepinage::default().with_line(plank_line![Plank::new(3000).unwrap()]);
+
+    let png = preview_png(&deck, &calepinage, &Preview::default()).unwrap();
+
+    assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+    assert_eq!(
+        Err("a preview can't have a zero width or plank width, nor the deck a zero length".to_string()),
+        preview_png(&deck, &calepinage, &Preview { width: 0, ..Preview::default() })
+    );
+}
+
+#[test]
+fn preview_should_refuse_images_over_the_pixel_limit() {
+    let deck = Deck::new(10, 500).unwrap();
+
+    assert_eq!(
+        Err("a preview can't have more than 16777216 pixels".to_string()),
+        preview_png(&deck, &Calepinage::default(), &Preview::default())
+    );
+}
@@ -0,0 +1,402 @@
+use crate::calepinage::{Calepinage, Junction, Line};
+
+/// Rules a layout has to follow, tuned per project.
+/// The default only forbids a junction right next to a junction of the previous line.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Rules {
+    pub stagger: StaggerRule,
+    pub staircase: Option<StaircaseRule>,
+    pub repetition: Option<RepetitionRule>,
+}
+
+impl Rules {
+    pub fn with_stagger(self, stagger: StaggerRule) -> Self {
+        Rules { stagger, ..self }
+    }
+
+    pub fn with_staircase(self, staircase: StaircaseRule) -> Self {
+        Rules {
+            staircase: Some(staircase),
+            ..self
+        }
+    }
+
+    pub fn with_repetition(self, repetition: RepetitionRule) -> Self {
+        Rules {
+            repetition: Some(repetition),
+            ..self
+        }
+    }
+
+    /// Whether the whole calepinage follows every rule.
+    pub fn accept(&self, calepinage: &Calepinage) -> bool {
+        self.stagger.find_violation(calepinage).is_none()
+            && self
+                .staircase
+                .as_ref()
+                .is_none_or(|staircase| staircase.find_staircases(calepinage).is_empty())
+            && self
+                .repetition
+                .as_ref()
+                .is_none_or(|repetition| repetition.find_violation(calepinage).is_none())
+    }
+
+    /// Tells whether a junction can't be added to the line following the calepinage,
+    /// looking at the calepinage once for all the junctions of the line.
+    pub fn forbidden_junctions<'a>(
+        &'a self,
+        calepinage: &Calepinage,
+    ) -> impl Fn(&Junction) -> bool + 'a {
+        let recent_junctions = self.stagger.recent_junctions(calepinage);
+        let staircase_junctions = self
+            .staircase
+            .as_ref()
+            .map(|staircase| staircase.previous_junctions(calepinage));
+        move |junction| {
+            self.stagger.forbids(junction, &recent_junctions)
+                || self
+                    .staircase
+                    .as_ref()
+                    .zip(staircase_junctions.as_ref())
+                    .is_some_and(|(staircase, previous_junctions)| {
+                        staircase.extends(junction, previous_junctions)
+                    })
+        }
+    }
+}
+
+// A junction must not come back within `lines` lines, `tolerance` included.
+// With lines = 2, this is forbidden whatever the tolerance (H-joint):
+//
+// |p1   |p2   |
+// |p3|p4   |p5|
+// |p6   |p7   |
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaggerRule {
+    pub lines: usize,
+    pub tolerance: usize,
+}
+
+impl Default for StaggerRule {
+    fn default() -> Self {
+        StaggerRule {
+            lines: 1,
+            tolerance: 0,
+        }
+    }
+}
+
+impl StaggerRule {
+    pub const MIN_LINES: usize = 1;
+
+    pub fn new(lines: usize, tolerance: usize) -> Result<Self, String> {
+        if lines < Self::MIN_LINES {
+            Err(format!("a stagger spans at least {} line", Self::MIN_LINES))
+        } else {
+            Ok(StaggerRule { lines, tolerance })
+        }
+    }
+
+    /// Junctions of the last `lines` lines of the calepinage, the ones the next line has to avoid.
+    pub fn recent_junctions(&self, calepinage: &Calepinage) -> Vec<Junction> {
+        self.junctions_before(&calepinage.0)
+    }
+
+    fn junctions_before(&self, lines: &[Line]) -> Vec<Junction> {
+        lines
+            .iter()
+            .rev()
+            .take(self.lines)
+            .flat_map(|line| line.compute_junction())
+            .collect()
+    }
+
+    pub fn forbids(&self, junction: &Junction, recent_junctions: &[Junction]) -> bool {
+        recent_junctions
+            .iter()
+            .any(|recent| junction.distance(recent) <= self.tolerance)
+    }
+
+    /// First junction breaking the rule, with the index of its line.
+    pub fn find_violation(&self, calepinage: &Calepinage) -> Option<(usize, Junction)> {
+        let Calepinage(lines) = calepinage;
+        lines.iter().enumerate().find_map(|(index, line)| {
+            let recent_junctions = self.junctions_before(&lines[..index]);
+            line.compute_junction()
+                .into_iter()
+                .find(|junction| self.forbids(junction, &recent_junctions))
+                .map(|junction| (index, junction))
+        })
+    }
+}
+
+// Junctions shifting by the same step over `lines` lines draw a visible diagonal.
+// With lines = 3, this is a staircase (junctions at 1, 2 and 3):
+//
+// |p1|p2            |
+// |p3   |p4         |
+// |p5      |p6      |
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaircaseRule {
+    pub lines: usize,
+    pub tolerance: usize,
+}
+
+/// Junctions forming a diagonal, one per line from `first_line`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Staircase {
+    pub first_line: usize,
+    pub junctions: Vec<Junction>,
+}
+
+impl StaircaseRule {
+    pub const MIN_LINES: usize = 3;
+
+    pub fn new(lines: usize, tolerance: usize) -> Result<Self, String> {
+        if lines < Self::MIN_LINES {
+            Err(format!("a staircase spans at least {} lines", Self::MIN_LINES))
+        } else {
+            Ok(StaircaseRule { lines, tolerance })
+        }
+    }
+
+    /// Longest runs of at least `lines` junctions shifting by the same step on each line.
+    pub fn find_staircases(&self, calepinage: &Calepinage) -> Vec<Staircase> {
+        let Calepinage(lines) = calepinage;
+        let junctions: Vec<Vec<Junction>> = lines.iter().map(Line::compute_junction).collect();
+        let mut staircases = vec![];
+        for (first_line, pair) in junctions.windows(2).enumerate() {
+            for start in &pair[0] {
+                for next in &pair[1] {
+                    let step = next.0 as isize - start.0 as isize;
+                    let continues_previous_line = first_line > 0
+                        && self
+                            .junction_near(&junctions[first_line - 1], start.0 as isize - step)
+                            .is_some();
+                    if step == 0 || continues_previous_line {
+                        continue;
+                    }
+                    let mut run = vec![start.clone(), next.clone()];
+                    while let Some(junction) = junctions
+                        .get(first_line + run.len())
+                        .and_then(|line| {
+                            self.junction_near(line, start.0 as isize + run.len() as isize * step)
+                        })
+                    {
+                        run.push(junction);
+                    }
+                    if run.len() >= self.lines {
+                        staircases.push(Staircase {
+                            first_line,
+                            junctions: run,
+                        });
+                    }
+                }
+            }
+        }
+        staircases
+    }
+
+    /// Whether a junction on the line following the calepinage would complete a staircase.
+    pub fn extends_staircase(&self, junction: &Junction, calepinage: &Calepinage) -> bool {
+        self.extends(junction, &self.previous_junctions(calepinage))
+    }
+
+    // Junctions of the lines a new junction could make a staircase with, the last line first.
+    fn previous_junctions(&self, calepinage: &Calepinage) -> Vec<Vec<Junction>> {
+        let Calepinage(lines) = calepinage;
+        if lines.len() + 1 < self.lines {
+            return vec![];
+        }
+        lines
+            .iter()
+            .rev()
+            .take(self.lines - 1)
+            .map(Line::compute_junction)
+            .collect()
+    }
+
+    fn extends(&self, junction: &Junction, previous_junctions: &[Vec<Junction>]) -> bool {
+        previous_junctions.first().is_some_and(|last_line| {
+            last_line.iter().any(|last| {
+                let step = junction.0 as isize - last.0 as isize;
+                step != 0
+                    && previous_junctions
+                        .iter()
+                        .enumerate()
+                        .skip(1)
+                        .all(|(distance, line)| {
+                            self.junction_near(line, junction.0 as isize - (distance as isize + 1) * step)
+                                .is_some()
+                        })
+            })
+        })
+    }
+
+    fn junction_near(&self, junctions: &[Junction], position: isize) -> Option<Junction> {
+        junctions
+            .iter()
+            .find(|junction| (junction.0 as isize - position).unsigned_abs() <= self.tolerance)
+            .cloned()
+    }
+}
+
+/// A line must not have the same sequence of plank lengths, as is or mirrored,
+/// as any of the previous `lines` lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepetitionRule {
+    pub lines: usize,
+}
+
+impl RepetitionRule {
+    pub const MIN_LINES: usize = 1;
+
+    pub fn new(lines: usize) -> Result<Self, String> {
+        if lines < Self::MIN_LINES {
+            Err(format!("a repetition spans at least {} line", Self::MIN_LINES))
+        } else {
+            Ok(RepetitionRule { lines })
+        }
+    }
+
+    /// Whether the line would repeat a recent line when added to the calepinage.
+    pub fn repeats(&self, line: &Line, calepinage: &Calepinage) -> bool {
+        self.repeats_after(line, &calepinage.0)
+    }
+
+    fn repeats_after(&self, line: &Line, lines: &[Line]) -> bool {
+        lines.iter().rev().take(self.lines).any(|recent| recent.looks_like(line))
+    }
+
+    /// Index of the first line repeating a recent one.
+    pub fn find_violation(&self, calepinage: &Calepinage) -> Option<usize> {
+        let Calepinage(lines) = calepinage;
+        (1..lines.len()).find(|&index| self.repeats_after(&lines[index], &lines[..index]))
+    }
+}
+
+#[cfg(test)]
+use crate::calepinage::Plank;
+#[cfg(test)]
+use crate::plank_line;
+
+#[test]
+fn default_stagger_should_only_look_at_previous_line() {
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(2).unwrap()])
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(1).unwrap()])
+        .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(2).unwrap()]);
+
+    assert_eq!(None, StaggerRule::default().find_violation(&calepinage));
+}
+
+#[test]
+fn stagger_on_two_lines_should_detect_h_joint() {
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(2).unwrap()])
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(1).unwrap()])
+        .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(2).unwrap()]);
+
+    assert_eq!(
+        Some((2, Junction(1))),
+        StaggerRule::new(2, 0).unwrap().find_violation(&calepinage)
+    );
+}
+
+#[test]
+fn stagger_tolerance_should_forbid_close_junctions() {
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(3).unwrap(), Plank::new(3).unwrap()])
+        .with_line(plank_line![Plank::new(4).unwrap(), Plank::new(2).unwrap()]);
+
+    assert_eq!(None, StaggerRule::new(1, 0).unwrap().find_violation(&calepinage));
+    assert_eq!(
+        Some((1, Junction(4))),
+        StaggerRule::new(1, 1).unwrap().find_violation(&calepinage)
+    );
+}
+
+#[test]
+fn stagger_needs_a_line() {
+    assert!(StaggerRule::new(0, 0).is_err());
+    assert!(StaggerRule::new(1, 0).is_ok());
+}
+
+#[test]
+fn staircase_needs_three_lines() {
+    assert!(StaircaseRule::new(2, 0).is_err());
+    assert!(StaircaseRule::new(3, 0).is_ok());
+}
+
+#[test]
+fn should_find_staircase() {
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(5).unwrap()])
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(4).unwrap()])
+        .with_line(plank_line![Plank::new(3).unwrap(), Plank::new(3).unwrap()])
+        .with_line(plank_line![Plank::new(4).unwrap(), Plank::new(2).unwrap()]);
+
+    let staircases = StaircaseRule::new(3, 0).unwrap().find_staircases(&calepinage);
+
+    assert_eq!(
+        vec![Staircase {
+            first_line: 0,
+            junctions: vec![Junction(1), Junction(2), Junction(3), Junction(4)],
+        }],
+        staircases
+    );
+}
+
+#[test]
+fn should_not_find_staircase_with_irregular_steps() {
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(5).unwrap()])
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(4).unwrap()])
+        .with_line(plank_line![Plank::new(4).unwrap(), Plank::new(2).unwrap()]);
+
+    assert!(StaircaseRule::new(3, 0)
+        .unwrap()
+        .find_staircases(&calepinage)
+        .is_empty());
+    assert!(!StaircaseRule::new(3, 1)
+        .unwrap()
+        .find_staircases(&calepinage)
+        .is_empty());
+}
+
+#[test]
+fn junction_should_extend_staircase() {
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(3).unwrap(), Plank::new(3).unwrap()])
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(4).unwrap()]);
+    let rule = StaircaseRule::new(3, 0).unwrap();
+
+    assert!(rule.extends_staircase(&Junction(1), &calepinage));
+    assert!(!rule.extends_staircase(&Junction(4), &calepinage));
+}
+
+#[test]
+fn should_find_line_repeated_within_rule_distance() {
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(2).unwrap()])
+        .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(1).unwrap(), Plank::new(1).unwrap()])
+        .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(2).unwrap()]);
+
+    assert_eq!(None, RepetitionRule::new(1).unwrap().find_violation(&calepinage));
+    assert_eq!(Some(2), RepetitionRule::new(2).unwrap().find_violation(&calepinage));
+}
+
+#[test]
+fn repetition_needs_a_line() {
+    assert!(RepetitionRule::new(0).is_err());
+    assert!(RepetitionRule::new(1).is_ok());
+}
+
+#[test]
+fn mirrored_line_should_count_as_repeated() {
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(2).unwrap(), Plank::new(3).unwrap()])
+        .with_line(plank_line![Plank::new(3).unwrap(), Plank::new(2).unwrap(), Plank::new(1).unwrap()]);
+
+    assert_eq!(Some(1), RepetitionRule::new(1).unwrap().find_violation(&calepinage));
+}
@@ -0,0 +1,374 @@
+use std::collections::HashSet;
+
+use varisat::{ExtendFormula, Lit, Solver};
+
+use crate::calepinage::{count_lengths, gcd, Calepinage, CalepinageError, Deck, Line, Plank, PlankHeap};
+use crate::control::{Control, Outcome};
+use crate::rules::Rules;
+
+/// Lays the deck with a SAT solver: either every rule is followed or the planks are proven
+/// not to be enough, in which case the error is `CalepinageError::Infeasible`.
+/// The formula grows with the number of positions a junction can take on a line,
+/// so this is meant for hard instances of small decks rather than as a default strategy.
+pub fn calepine_with_sat(
+    plank_heap: PlankHeap,
+    deck: Deck,
+    rules: &Rules,
+) -> Result<Calepinage, CalepinageError> {
+    calepine_with_sat_and_control(plank_heap, deck, rules, &mut Control::default())
+        .map(|outcome| outcome.calepinage)
+}
+
+/// Like `calepine_with_sat`, checking `control` before each solve. A solve once started runs
+/// to its end; when interrupted, no layout following the rules has been found so the
+/// outcome has no lines.
+pub fn calepine_with_sat_and_control(
+    plank_heap: PlankHeap,
+    deck: Deck,
+    rules: &Rules,
+    control: &mut Control,
+) -> Result<Outcome, CalepinageError> {
+    let mut solver = Solver::new();
+    let encoding = Encoding::new(&mut solver, &plank_heap, &deck, rules);
+    // staircases and repeated lines are excluded once found in a solution,
+    // instead of encoding every possible one up front
+    loop {
+        if control.is_interrupted() {
+            return Ok(Outcome {
+                calepinage: Calepinage::default(),
+                interrupted: true,
+            });
+        }
+        let satisfiable = solver
+            .solve()
+            .expect("solver runs without proof output nor interruption");
+        if !satisfiable {
+            return Err(CalepinageError::Infeasible);
+        }
+        let model: HashSet<Lit> = solver.model().unwrap_or_default().into_iter().collect();
+        let calepinage = encoding.decode(&model, &plank_heap);
+        match encoding.blocking_clause(&calepinage, rules) {
+            Some(clause) => solver.add_clause(&clause),
+            None => {
+                control.report_layout(&calepinage, &plank_heap);
+                return Ok(Outcome {
+                    calepinage,
+                    interrupted: false,
+                });
+            }
+        }
+    }
+}
+
+// Positions are counted in `unit`, the greatest common divisor of the deck and plank lengths.
+// A line is a chain of segments from 0 to `positions`; a segment is a plank of one of the
+// distinct `lengths` starting at a position, and a cut is a junction at a position.
+struct Encoding {
+    unit: usize,
+    positions: usize,
+    // distinct lengths in units, with the number of planks of that length
+    lengths: Vec<(usize, usize)>,
+    // segments[line][start] = (index in lengths, literal)
+    segments: Vec<Vec<Vec<(usize, Lit)>>>,
+    // cuts[line][position], only inside the line
+    cuts: Vec<Vec<Option<Lit>>>,
+}
+
+impl Encoding {
+    fn new(solver: &mut Solver, plank_heap: &PlankHeap, deck: &Deck, rules: &Rules) -> Self {
+        let usable: Vec<usize> = plank_heap
+            .planks()
+            .iter()
+            .map(|plank| plank.length)
+            .filter(|&length| length > 0 && length <= deck.length)
+            .collect();
+        let unit = usable.iter().fold(deck.length, |unit, &length| gcd(unit, length));
+        let positions = deck.length / unit;
+        let lengths = count_lengths(usable.into_iter().map(|length| length / unit));
+
+        let mut encoding = Encoding {
+            unit,
+            positions,
+            lengths,
+            segments: vec![],
+            cuts: vec![],
+        };
+        for _ in 0..deck.width {
+            encoding.add_line(solver);
+        }
+        encoding.add_plank_counts(solver);
+        encoding.add_stagger(solver, rules);
+        encoding
+    }
+
+    fn add_line(&mut self, solver: &mut Solver) {
+        let positions = self.positions;
+        let segments: Vec<Vec<(usize, Lit)>> = (0..positions)
+            .map(|start| {
+                self.lengths
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (length, _))| start + length <= positions)
+                    .map(|(index, _)| (index, solver.new_lit()))
+                    .collect()
+            })
+            .collect();
+        let cuts: Vec<Option<Lit>> = (0..=positions)
+            .map(|position| {
+                if position > 0 && position < positions {
+                    Some(solver.new_lit())
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        let literals = |entries: &[(usize, Lit)]| entries.iter().map(|(_, lit)| *lit).collect::<Vec<Lit>>();
+        solver.add_clause(&literals(&segments[0]));
+        for starting in &segments {
+            at_most_one(solver, &literals(starting));
+        }
+        for (position, cut) in cuts.iter().enumerate() {
+            let cut = match cut {
+                Some(cut) => *cut,
+                None => continue,
+            };
+            let starting = literals(&segments[position]);
+            let ending: Vec<Lit> = self
+                .lengths
+                .iter()
+                .enumerate()
+                .filter(|(_, (length, _))| *length <= position)
+                .filter_map(|(index, (length, _))| {
+                    segments[position - length]
+                        .iter()
+                        .find(|(segment_index, _)| *segment_index == index)
+                        .map(|(_, lit)| *lit)
+                })
+                .collect();
+            for side in [starting, ending].iter() {
+                solver.add_clause(&[vec![!cut], side.clone()].concat());
+                for lit in side {
+                    solver.add_clause(&[!*lit, cut]);
+                }
+            }
+        }
+        self.segments.push(segments);
+        self.cuts.push(cuts);
+    }
+
+    fn add_plank_counts(&self, solver: &mut Solver) {
+        for (index, (_, count)) in self.lengths.iter().enumerate() {
+            let lits: Vec<Lit> = self
+                .segments
+                .iter()
+                .flatten()
+                .flatten()
+                .filter(|(segment_index, _)| *segment_index == index)
+                .map(|(_, lit)| *lit)
+                .collect();
+            at_most(solver, &lits, *count);
+        }
+    }
+
+    fn add_stagger(&self, solver: &mut Solver, rules: &Rules) {
+        let stagger = &rules.stagger;
+        for line in 0..self.cuts.len() {
+            for earlier in line.saturating_sub(stagger.lines)..line {
+                for (position, cut) in self.cuts[line].iter().enumerate() {
+                    for (earlier_position, earlier_cut) in self.cuts[earlier].iter().enumerate() {
+                        if let (Some(cut), Some(earlier_cut)) = (cut, earlier_cut) {
+                            if position.abs_diff(earlier_position) * self.unit <= stagger.tolerance {
+                                solver.add_clause(&[!*cut, !*earlier_cut]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn decode(&self, model: &HashSet<Lit>, plank_heap: &PlankHeap) -> Calepinage {
+        let mut planks: Vec<Plank> = plank_heap.planks().to_vec();
+        let mut calepinage = Calepinage::default();
+        for segments in &self.segments {
+            let mut line = Line::default();
+            let mut position = 0;
+            while position < self.positions {
+                let length = segments[position]
+                    .iter()
+                    .find(|(_, lit)| model.contains(lit))
+                    .map(|(index, _)| self.lengths[*index].0 * self.unit)
+                    .expect("a segment starts at every cut of a line");
+                let index = planks
+                    .iter()
+                    .position(|plank| plank.length == length)
+                    .expect("lines use no more planks than the heap has");
+                line = line.with_plank(planks.remove(index));
+                position += length / self.unit;
+            }
+            calepinage = calepinage.with_line(line);
+        }
+        calepinage
+    }
+
+    // Clause excluding the first staircase or repeated line of a solution, if any.
+    fn blocking_clause(&self, calepinage: &Calepinage, rules: &Rules) -> Option<Vec<Lit>> {
+        let staircase = rules.staircase.as_ref().and_then(|staircase| {
+            staircase
+                .find_staircases(calepinage)
+                .into_iter()
+                .next()
+                .map(|found| {
+                    found
+                        .junctions
+                        .iter()
+                        .take(staircase.lines)
+                        .enumerate()
+                        .filter_map(|(distance, junction)| {
+                            self.cuts[found.first_line + distance][junction.0 / self.unit]
+                        })
+                        .map(|cut| !cut)
+                        .collect()
+                })
+        });
+        let repetition = || {
+            rules.repetition.as_ref().and_then(|repetition| {
+                let line = repetition.find_violation(calepinage)?;
+                let Calepinage(lines) = calepinage;
+                let earlier = (line.saturating_sub(repetition.lines)..line)
+                    .find(|&earlier| lines[earlier].looks_like(&lines[line]))?;
+                Some(
+                    [self.used_segments(line, &lines[line]), self.used_segments(earlier, &lines[earlier])]
+                        .concat()
+                        .into_iter()
+                        .map(|lit| !lit)
+                        .collect(),
+                )
+            })
+        };
+        staircase.or_else(repetition)
+    }
+
+    fn used_segments(&self, line_index: usize, line: &Line) -> Vec<Lit> {
+        let mut position = 0;
+        line.lengths()
+            .into_iter()
+            .filter_map(|length| {
+                let length = length / self.unit;
+                let lit = self.segments[line_index][position]
+                    .iter()
+                    .find(|(index, _)| self.lengths[*index].0 == length)
+                    .map(|(_, lit)| *lit);
+                position += length;
+                lit
+            })
+            .collect()
+    }
+}
+
+fn at_most_one(solver: &mut Solver, lits: &[Lit]) {
+    for (index, first) in lits.iter().enumerate() {
+        for second in &lits[index + 1..] {
+            solver.add_clause(&[!*first, !*second]);
+        }
+    }
+}
+
+// Sequential counter encoding (Sinz, 2005): counters[i][j] is true
+// when at least j + 1 of the first i + 1 literals are true.
+fn at_most(solver: &mut Solver, lits: &[Lit], count: usize) {
+    if count >= lits.len() {
+        return;
+    }
+    if count == 0 {
+        for lit in lits {
+            solver.add_clause(&[!*lit]);
+        }
+        return;
+    }
+    let counters: Vec<Vec<Lit>> = (0..lits.len() - 1)
+        .map(|_| (0..count).map(|_| solver.new_lit()).collect())
+        .collect();
+    solver.add_clause(&[!lits[0], counters[0][0]]);
+    for counter in &counters[0][1..] {
+        solver.add_clause(&[!*counter]);
+    }
+    for index in 1..lits.len() - 1 {
+        solver.add_clause(&[!lits[index], counters[index][0]]);
+        solver.add_clause(&[!counters[index - 1][0], counters[index][0]]);
+        for at_least in 1..count {
+            solver.add_clause(&[!lits[index], !counters[index - 1][at_least - 1], counters[index][at_least]]);
+            solver.add_clause(&[!counters[index - 1][at_least], counters[index][at_least]]);
+        }
+        solver.add_clause(&[!lits[index], !counters[index - 1][count - 1]]);
+    }
+    solver.add_clause(&[!lits[lits.len() - 1], !counters[lits.len() - 2][count - 1]]);
+}
+
+#[cfg(test)]
+use crate::calepinage::calepine;
+#[cfg(test)]
+use crate::rules::{RepetitionRule, StaggerRule, StaircaseRule};
+
+#[test]
+fn sat_should_solve_heap_greedy_cannot() {
+    let deck = Deck::new(5, 3).unwrap();
+    let plank_heap = || PlankHeap::default().add(3, 1).add(3, 2).add(2, 3);
+
+    assert!(calepine(plank_heap(), deck.clone()).is_err());
+    let calepinage = calepine_with_sat(plank_heap(), deck, &Rules::default()).unwrap();
+    assert!(Rules::default().accept(&calepinage));
+    assert_eq!(3, calepinage.0.len());
+}
+
+#[test]
+fn sat_should_prove_infeasibility() {
+    let deck = Deck::new(4, 2).unwrap();
+    let plank_heap = PlankHeap::default().add(4, 2).add(1, 3);
+
+    let result = calepine_with_sat(plank_heap, deck, &Rules::default());
+
+    assert_eq!(Err(CalepinageError::Infeasible), result);
+}
+
+#[test]
+fn sat_should_follow_every_rule() {
+    let deck = Deck::new(9, 4).unwrap();
+    let plank_heap = PlankHeap::default().add(4, 1).add(4, 2).add(6, 3).add(4, 4);
+    let rules = Rules::default()
+        .with_stagger(StaggerRule::new(2, 0).unwrap())
+        .with_staircase(StaircaseRule::new(3, 0).unwrap())
+        .with_repetition(RepetitionRule::new(3).unwrap());
+
+    let calepinage = calepine_with_sat(plank_heap, deck, &rules).unwrap();
+
+    assert!(rules.accept(&calepinage));
+}
+
+#[test]
+fn at_most_should_limit_true_literals() {
+    let mut solver = Solver::new();
+    let lits: Vec<Lit> = (0..4).map(|_| solver.new_lit()).collect();
+    at_most(&mut solver, &lits, 2);
+    solver.add_clause(&[lits[0]]);
+    solver.add_clause(&[lits[1]]);
+
+    assert_eq!(Ok(true), solver.solve().map_err(|_| ()));
+    solver.add_clause(&[lits[3]]);
+    assert_eq!(Ok(false), solver.solve().map_err(|_| ()));
+}
+
+#[test]
+fn sat_should_not_solve_once_interrupted() {
+    use std::time::Duration;
+
+    let deck = Deck::new(5, 3).unwrap();
+    let plank_heap = PlankHeap::default().add(3, 1).add(3, 2).add(2, 3);
+    let mut control = Control::default().with_budget(Duration::from_secs(0));
+
+    let outcome = calepine_with_sat_and_control(plank_heap, deck, &Rules::default(), &mut control).unwrap();
+
+    assert!(outcome.interrupted);
+    assert!(outcome.calepinage.0.is_empty());
+}
@@ -0,0 +1,221 @@
+use std::cmp::Reverse;
+use std::fmt;
+
+use crate::calepinage::{Calepinage, Plank, PlankHeap};
+use crate::metrics::{min_stagger, repetition};
+
+/// How much each criterion of a `Score` counts; the weighted score of a layout is
+/// the lower the better. A piece is short when shorter than `short_length`.
+/// `optimise::anneal` minimises any score, the beam search ranks its layouts with the weights
+/// of its `Control`, and the PDF and XLSX reports print the score of the layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Weights {
+    pub waste: f64,
+    pub cuts: f64,
+    pub stagger: f64,
+    pub short_pieces: f64,
+    pub repetition: f64,
+    pub short_length: usize,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            waste: 1.0,
+            cuts: 1.0,
+            stagger: 1.0,
+            short_pieces: 1.0,
+            repetition: 1.0,
+            short_length: 0,
+        }
+    }
+}
+
+impl Weights {
+    pub fn with_waste(self, waste: f64) -> Self {
+        Weights { waste, ..self }
+    }
+
+    pub fn with_cuts(self, cuts: f64) -> Self {
+        Weights { cuts, ..self }
+    }
+
+    pub fn with_stagger(self, stagger: f64) -> Self {
+        Weights { stagger, ..self }
+    }
+
+    pub fn with_short_pieces(self, short_pieces: f64, short_length: usize) -> Self {
+        Weights {
+            short_pieces,
+            short_length,
+            ..self
+        }
+    }
+
+    pub fn with_repetition(self, repetition: f64) -> Self {
+        Weights { repetition, ..self }
+    }
+
+    /// Weighted score of a layout of planks from `plank_heap`, as strategies minimise it.
+    pub fn evaluate(&self, calepinage: &Calepinage, plank_heap: &PlankHeap) -> f64 {
+        Score::new(calepinage, plank_heap, self.short_length).weighted(self)
+    }
+}
+
+// Pieces taken from each plank of the heap, in the order of the heap. Pieces with an id come
+// from the plank of that id; the others are whole planks of their length when there are
+// enough, or else are cut one per plank from the shortest plank left long enough.
+// Pieces from none of the planks are left out.
+pub(crate) fn pieces_by_stock<'a>(plank_heap: &PlankHeap, pieces: &[&'a Plank]) -> Vec<Vec<&'a Plank>> {
+    let planks = plank_heap.planks();
+    let mut cut_from: Vec<Vec<&Plank>> = vec![vec![]; planks.len()];
+    let mut anonymous = vec![];
+    for &piece in pieces {
+        let stock = piece
+            .id
+            .as_ref()
+            .and_then(|id| planks.iter().position(|plank| plank.id.as_ref() == Some(id)));
+        match stock {
+            Some(index) => cut_from[index].push(piece),
+            None => anonymous.push(piece),
+        }
+    }
+    let is_free = |cut_from: &[Vec<&Plank>], index: usize| planks[index].id.is_none() && cut_from[index].is_empty();
+    anonymous.retain(|piece| {
+        match (0..planks.len()).find(|&index| is_free(&cut_from, index) && planks[index].length == piece.length) {
+            Some(index) => {
+                cut_from[index].push(piece);
+                false
+            }
+            None => true,
+        }
+    });
+    anonymous.sort_by_key(|piece| Reverse(piece.length));
+    for piece in anonymous {
+        let shortest = (0..planks.len())
+            .filter(|&index| is_free(&cut_from, index) && planks[index].length >= piece.length)
+            .min_by_key(|&index| planks[index].length);
+        if let Some(index) = shortest {
+            cut_from[index].push(piece);
+        }
+    }
+    cut_from
+}
+
+/// What a layout of planks taken from a heap is judged on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Score {
+    /// Length of the offcuts: what is left of the planks pieces are cut from, pieces with
+    /// an id coming from the plank of that id. Planks left whole are not waste.
+    pub waste: usize,
+    /// Pieces of the layout that are not a whole plank of the heap.
+    pub cuts: usize,
+    /// Smallest stagger between neighbour lines, the length of a line when no lines have junctions to compare.
+    pub min_stagger: usize,
+    pub short_pieces: usize,
+    pub repetition: f64,
+}
+
+impl Score {
+    pub fn new(calepinage: &Calepinage, plank_heap: &PlankHeap, short_length: usize) -> Self {
+        let Calepinage(lines) = calepinage;
+        let pieces: Vec<&Plank> = lines.iter().flat_map(|line| line.0.iter()).collect();
+
+        let cut_from = pieces_by_stock(plank_heap, &pieces);
+        let whole_planks = plank_heap
+            .planks()
+            .iter()
+            .zip(&cut_from)
+            .filter(|(plank, cut)| cut.len() == 1 && cut[0].length == plank.length)
+            .count();
+        let waste = plank_heap
+            .planks()
+            .iter()
+            .zip(&cut_from)
+            .filter(|(_, cut)| !cut.is_empty())
+            .map(|(plank, cut)| plank.length.saturating_sub(cut.iter().map(|piece| piece.length).sum()))
+            .sum();
+        let line_length = lines
+            .first()
+            .map(|line| line.lengths().iter().sum())
+            .unwrap_or(0);
+
+        Score {
+            waste,
+            cuts: pieces.len() - whole_planks,
+            min_stagger: min_stagger(calepinage).unwrap_or(line_length),
+            short_pieces: pieces.iter().filter(|piece| piece.length < short_length).count(),
+            repetition: repetition(calepinage),
+        }
+    }
+
+    /// Lower is better: every criterion adds to the score but the stagger, which takes from it.
+    pub fn weighted(&self, weights: &Weights) -> f64 {
+        weights.waste * self.waste as f64 + weights.cuts * self.cuts as f64
+            - weights.stagger * self.min_stagger as f64
+            + weights.short_pieces * self.short_pieces as f64
+            + weights.repetition * self.repetition
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "waste = {}, cuts = {}, min stagger = {}, short pieces = {}, repetition = {:.2}",
+            self.waste, self.cuts, self.min_stagger, self.short_pieces, self.repetition
+        )
+    }
+}
+
+#[cfg(test)]
+use crate::calepinage::Line;
+#[cfg(test)]
+use crate::plank_line;
+
+#[test]
+fn score_should_count_waste_cuts_and_short_pieces() {
+    let plank_heap = PlankHeap::default().add(1, 5).add(3, 3).add(2, 1);
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(3).unwrap(), Plank::new(3).unwrap()])
+        .with_line(plank_line![Plank::new(4).unwrap(), Plank::new(2).unwrap()]);
+
+    let score = Score::new(&calepinage, &plank_heap, 3);
+
+    assert_eq!(
+        "waste = 2, cuts = 2, min stagger = 1, short pieces = 1, repetition = 0.00",
+        score.to_string()
+    );
+}
+
+#[test]
+fn weights_should_prefer_larger_stagger() {
+    let plank_heap = PlankHeap::default().add(4, 2).add(2, 1);
+    let aligned = Calepinage::default()
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(1).unwrap()])
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(1).unwrap()]);
+    let staggered = Calepinage::default()
+        .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(1).unwrap()])
+        .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(2).unwrap()]);
+    let weights = Weights::default().with_waste(0.0);
+
+    assert!(weights.evaluate(&staggered, &plank_heap) < weights.evaluate(&aligned, &plank_heap));
+}
+
+#[test]
+fn waste_should_not_count_planks_left_whole() {
+    let plank_heap = PlankHeap::default()
+        .add(1, 6)
+        .add_plank(Plank::new(5).unwrap().with_id("A"))
+        .add(2, 4);
+    let calepinage = Calepinage::default().with_line(plank_line![
+        Plank::new(4).unwrap().with_id("A"),
+        Plank::new(4).unwrap()
+    ]);
+
+    let score = Score::new(&calepinage, &plank_heap, 0);
+
+    assert_eq!((1, 1), (score.waste, score.cuts));
+    let weights = Weights::default();
+    assert!(weights.clone().with_waste(10.0).evaluate(&calepinage, &plank_heap) > weights.evaluate(&calepinage, &plank_heap));
+}
@@ -0,0 +1,334 @@
+use rust_xlsxwriter::utility::column_number_to_name;
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+
+use crate::calepinage::{Calepinage, Plank, PlankHeap};
+use crate::score::{pieces_by_stock, Score, Weights};
+
+const LAYOUT_SHEET: &str = "Layout";
+const CUT_LIST_SHEET: &str = "Cut list";
+const INVENTORY_SHEET: &str = "Inventory";
+const METRICS_SHEET: &str = "Metrics";
+
+/// Workbook of the calepinage: the lengths laid line by line, the cut list, the planks of
+/// `plank_heap` left over and the metrics of the layout, totals being formulas.
+pub fn to_xlsx(calepinage: &Calepinage, plank_heap: &PlankHeap) -> Result<Vec<u8>, String> {
+    to_xlsx_with_weights(calepinage, plank_heap, &Weights::default())
+}
+
+/// Like `to_xlsx`, the metrics giving the score of the layout under `weights`.
+pub fn to_xlsx_with_weights(
+    calepinage: &Calepinage,
+    plank_heap: &PlankHeap,
+    weights: &Weights,
+) -> Result<Vec<u8>, String> {
+    workbook(&sheets(calepinage, plank_heap, weights))
+        .and_then(|mut workbook| workbook.save_to_buffer())
+        .map_err(|error| error.to_string())
+}
+
+// What a cell holds.
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+    Text(String),
+    Number(f64),
+    Formula(String),
+}
+
+// Cells of a worksheet by row and column from 0, bold or not; a frozen header stays in view.
+struct Sheet {
+    name: &'static str,
+    frozen_header: bool,
+    cells: Vec<(u32, u16, Cell, bool)>,
+}
+
+impl Sheet {
+    fn new(name: &'static str) -> Self {
+        Sheet {
+            name,
+            frozen_header: false,
+            cells: vec![],
+        }
+    }
+
+    fn set(&mut self, row: u32, column: u16, cell: Cell) {
+        self.cells.push((row, column, cell, false));
+    }
+
+    fn set_bold(&mut self, row: u32, column: u16, cell: Cell) {
+        self.cells.push((row, column, cell, true));
+    }
+
+    #[cfg(test)]
+    fn cell(&self, row: u32, column: u16) -> Option<&Cell> {
+        self.cells
+            .iter()
+            .find(|(cell_row, cell_column, _, _)| (*cell_row, *cell_column) == (row, column))
+            .map(|(_, _, cell, _)| cell)
+    }
+}
+
+fn sheets(calepinage: &Calepinage, plank_heap: &PlankHeap, weights: &Weights) -> Vec<Sheet> {
+    let (cut_list, planks_laid) = cut_list(calepinage);
+    let (inventory, planks_left) = inventory(&remaining(plank_heap, calepinage));
+    let metrics = metrics(calepinage, plank_heap, weights, planks_laid, planks_left);
+    vec![layout(calepinage), cut_list, inventory, metrics]
+}
+
+fn workbook(sheets: &[Sheet]) -> Result<Workbook, XlsxError> {
+    let bold = Format::new().set_bold();
+    let mut workbook = Workbook::new();
+    for sheet in sheets {
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(sheet.name)?;
+        if sheet.frozen_header {
+            worksheet.set_freeze_panes(1, 0)?;
+        }
+        for (row, column, cell, is_bold) in &sheet.cells {
+            write_cell(worksheet, *row, *column, cell, Some(&bold).filter(|_| *is_bold))?;
+        }
+    }
+    Ok(workbook)
+}
+
+fn write_cell(
+    sheet: &mut Worksheet,
+    row: u32,
+    column: u16,
+    cell: &Cell,
+    format: Option<&Format>,
+) -> Result<(), XlsxError> {
+    match (cell, format) {
+        (Cell::Text(text), Some(format)) => sheet.write_string_with_format(row, column, text, format),
+        (Cell::Text(text), None) => sheet.write_string(row, column, text),
+        (Cell::Number(number), Some(format)) => sheet.write_number_with_format(row, column, *number, format),
+        (Cell::Number(number), None) => sheet.write_number(row, column, *number),
+        (Cell::Formula(formula), Some(format)) => {
+            sheet.write_formula_with_format(row, column, formula.as_str(), format)
+        }
+        (Cell::Formula(formula), None) => sheet.write_formula(row, column, formula.as_str()),
+    }?;
+    Ok(())
+}
+
+fn text(text: &str) -> Cell {
+    Cell::Text(text.to_string())
+}
+
+// A row per line: its length, as the sum of its planks which follow in numbered columns.
+fn layout(calepinage: &Calepinage) -> Sheet {
+    let mut sheet = Sheet::new(LAYOUT_SHEET);
+    sheet.set_bold(0, 0, text("Line"));
+    sheet.set_bold(0, 1, text("Length (mm)"));
+    let widest = calepinage.0.iter().map(|line| line.0.len()).max().unwrap_or(1).max(1);
+    for column in 0..widest {
+        sheet.set_bold(0, 2 + column as u16, Cell::Text(format!("Plank {} (mm)", column + 1)));
+    }
+    let last_column = column_number_to_name(1 + widest as u16);
+    for (index, line) in calepinage.0.iter().enumerate() {
+        let row = 1 + index as u32;
+        sheet.set(row, 0, Cell::Number((index + 1) as f64));
+        sheet.set(row, 1, Cell::Formula(format!("=SUM(C{}:{}{})", row + 1, last_column, row + 1)));
+        for (column, length) in line.lengths().into_iter().enumerate() {
+            sheet.set(row, 2 + column as u16, Cell::Number(length as f64));
+        }
+    }
+    total(&mut sheet, calepinage.0.len(), &[1]);
+    sheet
+}
+
+// A row per plank, line by line, with the number of rows.
+fn cut_list(calepinage: &Calepinage) -> (Sheet, usize) {
+    let mut sheet = Sheet::new(CUT_LIST_SHEET);
+    header(&mut sheet, &["Line", "Position", "Plank", "Length (mm)", "Batch", "Supplier", "Grade"]);
+    let mut rows = 0;
+    for (line_index, line) in calepinage.0.iter().enumerate() {
+        for (position, plank) in line.0.iter().enumerate() {
+            let row = 1 + rows as u32;
+            sheet.set(row, 0, Cell::Number((line_index + 1) as f64));
+            sheet.set(row, 1, Cell::Number((position + 1) as f64));
+            let name = plank.id.clone().unwrap_or_else(|| format!("#{}", rows + 1));
+            plank_cells(&mut sheet, row, 2, Some(&name), plank);
+            rows += 1;
+        }
+    }
+    total(&mut sheet, rows, &[3]);
+    (sheet, rows)
+}
+
+// A row per plank left, named by its id if any, with the number of rows.
+fn inventory(planks: &[&Plank]) -> (Sheet, usize) {
+    let mut sheet = Sheet::new(INVENTORY_SHEET);
+    header(&mut sheet, &["Plank", "Length (mm)", "Batch", "Supplier", "Grade"]);
+    for (index, plank) in planks.iter().enumerate() {
+        plank_cells(&mut sheet, 1 + index as u32, 0, plank.id.as_deref(), plank);
+    }
+    total(&mut sheet, planks.len(), &[1]);
+    (sheet, planks.len())
+}
+
+fn metrics(
+    calepinage: &Calepinage,
+    plank_heap: &PlankHeap,
+    weights: &Weights,
+    planks_laid: usize,
+    planks_left: usize,
+) -> Sheet {
+    let mut sheet = Sheet::new(METRICS_SHEET);
+    header(&mut sheet, &["Metric", "Value"]);
+    let score = Score::new(calepinage, plank_heap, weights.short_length);
+    let formulas = [
+        ("Lines", format!("=COUNT('{}'!A2:A{})", LAYOUT_SHEET, calepinage.0.len() + 1)),
+        ("Planks laid", format!("=COUNT('{}'!D2:D{})", CUT_LIST_SHEET, planks_laid + 1)),
+        ("Length laid (mm)", format!("=SUM('{}'!D2:D{})", CUT_LIST_SHEET, planks_laid + 1)),
+        ("Planks left", format!("=COUNT('{}'!B2:B{})", INVENTORY_SHEET, planks_left + 1)),
+        ("Length left (mm)", format!("=SUM('{}'!B2:B{})", INVENTORY_SHEET, planks_left + 1)),
+    ];
+    for (index, (name, formula)) in formulas.iter().enumerate() {
+        sheet.set(1 + index as u32, 0, text(name));
+        sheet.set(1 + index as u32, 1, Cell::Formula(formula.clone()));
+    }
+    let values = [
+        ("Stock length (mm)", plank_heap.total_length() as f64),
+        ("Waste (mm)", score.waste as f64),
+        ("Cuts", score.cuts as f64),
+        ("Min stagger (mm)", score.min_stagger as f64),
+        ("Short pieces", score.short_pieces as f64),
+        ("Repetition", score.repetition),
+        ("Score", score.weighted(weights)),
+    ];
+    for (index, (name, value)) in values.iter().enumerate() {
+        let row = (1 + formulas.len() + index) as u32;
+        sheet.set(row, 0, text(name));
+        sheet.set(row, 1, Cell::Number(*value));
+    }
+    sheet
+}
+
+fn header(sheet: &mut Sheet, titles: &[&str]) {
+    for (column, title) in titles.iter().enumerate() {
+        sheet.set_bold(0, column as u16, text(title));
+    }
+    sheet.frozen_header = true;
+}
+
+fn plank_cells(sheet: &mut Sheet, row: u32, column: u16, name: Option<&str>, plank: &Plank) {
+    if let Some(name) = name {
+        sheet.set(row, column, text(name));
+    }
+    sheet.set(row, column + 1, Cell::Number(plank.length as f64));
+    let metadata = [&plank.metadata.batch, &plank.metadata.supplier, &plank.metadata.grade];
+    for (offset, value) in metadata.iter().enumerate() {
+        if let Some(value) = value {
+            sheet.set(row, column + 2 + offset as u16, text(value));
+        }
+    }
+}
+
+// Sums of the `columns` below `rows` rows of planks or lines.
+fn total(sheet: &mut Sheet, rows: usize, columns: &[u16]) {
+    let row = 1 + rows as u32;
+    sheet.set_bold(row, 0, text("Total"));
+    for &column in columns {
+        let name = column_number_to_name(column);
+        sheet.set_bold(row, column, Cell::Formula(format!("=SUM({}2:{}{})", name, name, row)));
+    }
+}
+
+// Planks of the heap no piece of the calepinage comes from.
+fn remaining<'a>(plank_heap: &'a PlankHeap, calepinage: &Calepinage) -> Vec<&'a Plank> {
+    let pieces: Vec<&Plank> = calepinage.0.iter().flat_map(|line| line.0.iter()).collect();
+    plank_heap
+        .planks()
+        .iter()
+        .zip(pieces_by_stock(plank_heap, &pieces))
+        .filter(|(_, cut)| cut.is_empty())
+        .map(|(plank, _)| plank)
+        .collect()
+}
+
+#[cfg(test)]
+use crate::calepinage::Line;
+#[cfg(test)]
+use crate::plank_line;
+
+#[test]
+fn remaining_should_be_the_planks_not_laid() {
+    let plank_heap = PlankHeap::default()
+        .add_plank(Plank::new(3000).unwrap().with_id("B-1"))
+        .add_plank(Plank::new(3000).unwrap().with_id("B-2"))
+        .add(2, 1000);
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(2000).unwrap().with_id("B-1"), Plank::new(1000).unwrap()])
+        .with_line(plank_line![Plank::new(1000).unwrap().with_id("B-1"), Plank::new(2000).unwrap()]);
+
+    let left: Vec<(Option<String>, usize)> = remaining(&plank_heap, &calepinage)
+        .into_iter()
+        .map(|plank| (plank.id.clone(), plank.length))
+        .collect();
+
+    assert_eq!(vec![(Some("B-2".to_string()), 3000), (None, 1000)], left);
+}
+
+#[test]
+fn xlsx_should_be_a_zip_archive() {
+    let plank_heap = PlankHeap::default().add(2, 2000).add(2, 1000);
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![Plank::new(2000).unwrap(), Plank::new(1000).unwrap()])
+        .with_line(plank_line![Plank::new(1000).unwrap(), Plank::new(2000).unwrap()]);
+
+    let xlsx = to_xlsx(&calepinage, &plank_heap).unwrap();
+
+    assert!(xlsx.starts_with(b"PK\x03\x04"));
+}
+
+#[test]
+fn remaining_should_count_planks_cut_into_pieces_without_id_as_laid() {
+    let plank_heap = PlankHeap::default().add(2, 3000).add(1, 1000);
+    let calepinage =
+        Calepinage::default().with_line(plank_line![Plank::new(2500).unwrap(), Plank::new(500).unwrap()]);
+
+    let left: Vec<usize> = remaining(&plank_heap, &calepinage).into_iter().map(|plank| plank.length).collect();
+
+    assert_eq!(vec![3000], left);
+}
+
+#[test]
+fn sheets_should_hold_the_layout_cut_list_inventory_and_metrics() {
+    let plank_heap = PlankHeap::default()
+        .add_plank(Plank::new(2000).unwrap().with_id("B-0412").with_batch("2021-06"))
+        .add(1, 2000)
+        .add(3, 1000);
+    let calepinage = Calepinage::default()
+        .with_line(plank_line![
+            Plank::new(2000).unwrap().with_id("B-0412").with_batch("2021-06"),
+            Plank::new(1000).unwrap()
+        ])
+        .with_line(plank_line![Plank::new(1000).unwrap(), Plank::new(2000).unwrap()]);
+
+    let sheets = sheets(&calepinage, &plank_heap, &Weights::default());
+
+    let names: Vec<&str> = sheets.iter().map(|sheet| sheet.name).collect();
+    assert_eq!(vec![LAYOUT_SHEET, CUT_LIST_SHEET, INVENTORY_SHEET, METRICS_SHEET], names);
+    let (layout, cut_list, inventory, metrics) = (&sheets[0], &sheets[1], &sheets[2], &sheets[3]);
+    assert_eq!(Some(&text("Plank 2 (mm)")), layout.cell(0, 3));
+    assert_eq!(None, layout.cell(0, 4));
+    assert_eq!(Some(&Cell::Formula("=SUM(C3:D3)".to_string())), layout.cell(2, 1));
+    assert_eq!(Some(&Cell::Number(2000.0)), layout.cell(2, 3));
+    assert_eq!(Some(&Cell::Formula("=SUM(B2:B3)".to_string())), layout.cell(3, 1));
+    assert_eq!(Some(&text("B-0412")), cut_list.cell(1, 2));
+    assert_eq!(Some(&text("2021-06")), cut_list.cell(1, 4));
+    assert_eq!(Some(&text("#4")), cut_list.cell(4, 2));
+    assert_eq!(None, inventory.cell(1, 0));
+    assert_eq!(Some(&Cell::Number(1000.0)), inventory.cell(1, 1));
+    assert_eq!(Some(&text("Total")), inventory.cell(2, 0));
+    let metric = |name: &str| {
+        (1..20)
+            .find(|&row| metrics.cell(row, 0) == Some(&text(name)))
+            .and_then(|row| metrics.cell(row, 1))
+    };
+    assert_eq!(Some(&Cell::Formula("=COUNT('Cut list'!D2:D5)".to_string())), metric("Planks laid"));
+    assert_eq!(Some(&Cell::Formula("=SUM('Inventory'!B2:B2)".to_string())), metric("Length left (mm)"));
+    assert_eq!(Some(&Cell::Number(0.0)), metric("Waste (mm)"));
+    assert_eq!(Some(&Cell::Number(0.0)), metric("Cuts"));
+    assert_eq!(Some(&Cell::Number(1000.0)), metric("Min stagger (mm)"));
+}
@@ -1,8 +1,14 @@
 #[cfg(test)]
+#[allow(clippy::wrong_self_convention)]
 mod calepinage_test {
     use quickcheck::{Arbitrary, Gen, QuickCheck, TestResult};
+    use rust::beam::calepine_with_beam;
     use rust::calepinage::*;
     use rust::plank_line;
+    use rust::metrics::{min_stagger, repetition};
+    use rust::optimise::{anneal, improve_stagger};
+    use rust::random::{calepine_randomly, random_candidates};
+    use rust::rules::{RepetitionRule, Rules, StaggerRule, StaircaseRule};
     use spectral::prelude::*;
     use std::collections::HashSet;
 
@@ -13,7 +19,7 @@ mod calepinage_test {
 
         let actual = calepine(plank_heap, deck);
 
-        let expected = Calepinage(vec![Line(vec![Plank { length: 1 }])]);
+        let expected = Calepinage(vec![Line(vec![Plank::new(1).unwrap()])]);
         assert_that(&actual).is_ok().is_equal_to(expected);
     }
 
@@ -24,7 +30,7 @@ mod calepinage_test {
 
         let actual = calepine(plank_heap, deck);
 
-        let expected = Calepinage(vec![Line(vec![Plank { length: 1 }, Plank { length: 1 }])]);
+        let expected = Calepinage(vec![Line(vec![Plank::new(1).unwrap(), Plank::new(1).unwrap()])]);
         assert_that(&actual).is_ok().is_equal_to(expected);
     }
 
@@ -35,7 +41,7 @@ mod calepinage_test {
 
         let actual = calepine(plank_heap, deck);
 
-        let expected = Calepinage(vec![Line(vec![Plank { length: 1 }])]);
+        let expected = Calepinage(vec![Line(vec![Plank::new(1).unwrap()])]);
         assert_that(&actual).is_ok().is_equal_to(expected);
     }
 
@@ -47,7 +53,7 @@ mod calepinage_test {
         let Calepinage(actual) = calepine(plank_heap, deck).unwrap();
         let flattened: Vec<Plank> = actual.into_iter().flat_map(|Line(line)| line).collect();
 
-        let expected: Vec<Plank> = vec![Plank { length: 2 }, Plank { length: 1 }];
+        let expected: Vec<Plank> = vec![Plank::new(2).unwrap(), Plank::new(1).unwrap()];
         assert_that(&flattened).contains_all_of(&expected.iter());
     }
 
@@ -59,7 +65,7 @@ mod calepinage_test {
         let Calepinage(actual) = calepine(plank_heap, deck).unwrap();
         let flattened: Vec<Plank> = actual.into_iter().flat_map(|Line(line)| line).collect();
 
-        let expected: Vec<Plank> = vec![Plank { length: 3 }, Plank { length: 1 }];
+        let expected: Vec<Plank> = vec![Plank::new(3).unwrap(), Plank::new(1).unwrap()];
         assert_that(&flattened).contains_all_of(&expected.iter());
         assert_that(&flattened).has_length(2)
     }
@@ -72,7 +78,7 @@ mod calepinage_test {
         let actual = calepine(plank_heap, deck);
 
         let expected: Calepinage =
-            a_calepinage().with_line(plank_line![Plank { length: 3 }, Plank { length: 1 }]);
+            a_calepinage().with_line(plank_line![Plank::new(3).unwrap(), Plank::new(1).unwrap()]);
         assert_that(&actual).is_ok().is_equal_to(&expected);
     }
 
@@ -84,8 +90,8 @@ mod calepinage_test {
         let actual = calepine(plank_heap, deck);
 
         let expected: Calepinage = a_calepinage()
-            .with_line(plank_line![Plank { length: 1 }, Plank { length: 1 }])
-            .with_line(plank_line![Plank { length: 1 }, Plank { length: 1 }]);
+            .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(1).unwrap()])
+            .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(1).unwrap()]);
 
         assert_that(&actual).is_ok().is_equal_to(&expected);
     }
@@ -102,8 +108,8 @@ mod calepinage_test {
         let actual = calepine(plank_heap, deck);
 
         let expected: Calepinage = a_calepinage()
-            .with_line(plank_line![Plank { length: 2 }])
-            .with_line(plank_line![Plank { length: 1 }, Plank { length: 1 }]);
+            .with_line(plank_line![Plank::new(2).unwrap()])
+            .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(1).unwrap()]);
 
         assert_that(&actual).is_ok().is_equal_to(&expected);
     }
@@ -133,7 +139,6 @@ mod calepinage_test {
     }
 
     #[test]
-    #[ignore] //waiting fix for with_line
     fn should_invert_longest_plank_in_each_line() {
         let deck = Deck::new(3, 2).unwrap();
         let plank_heap = PlankHeap::default().add(2, 1).add(2, 2);
@@ -141,8 +146,8 @@ mod calepinage_test {
         let actual = calepine(plank_heap, deck);
 
         let expected: Calepinage = a_calepinage()
-            .with_line(plank_line![Plank { length: 2 }, Plank { length: 1 }])
-            .with_line(plank_line![Plank { length: 1 }, Plank { length: 2 }]);
+            .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(1).unwrap()])
+            .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(2).unwrap()]);
 
         assert_that(&actual).is_ok().is_equal_to(&expected);
     }
@@ -220,9 +225,7 @@ mod calepinage_test {
 
     impl PlankForTest {
         fn to_plank(self) -> Plank {
-            Plank {
-                length: self.length,
-            }
+            Plank::new(self.length).unwrap()
         }
     }
 
@@ -231,7 +234,6 @@ mod calepinage_test {
         let junctions_rhs: HashSet<Junction> = rhs.compute_junction().into_iter().collect();
         junctions_lhs
             .intersection(&junctions_rhs)
-            .into_iter()
             .cloned()
             .collect::<Vec<Junction>>()
     }
@@ -250,8 +252,8 @@ mod calepinage_test {
     #[test]
     fn check_adjacent_assertion_detect_specific_case() {
         let input: Calepinage = a_calepinage()
-            .with_line(plank_line![Plank { length: 2 }, Plank { length: 1 }])
-            .with_line(plank_line![Plank { length: 1 }, Plank { length: 1 }, Plank { length: 1 }]);
+            .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(1).unwrap()])
+            .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(1).unwrap(), Plank::new(1).unwrap()]);
         assert_that(&assert_calepinage_has_no_adjacent_junction(&input)).is_false();
     }*/
 
@@ -283,6 +285,302 @@ mod calepinage_test {
         list_junctions.first().cloned()
     }
 
+    #[derive(Clone, Debug)]
+    struct StaggerRuleForTest {
+        lines: usize,
+        tolerance: usize,
+    }
+
+    impl Arbitrary for StaggerRuleForTest {
+        fn arbitrary(g: &mut Gen) -> Self {
+            StaggerRuleForTest {
+                lines: usize::arbitrary(g) % 4 + 1,
+                tolerance: usize::arbitrary(g) % 4,
+            }
+        }
+    }
+
+    impl StaggerRuleForTest {
+        fn to_stagger_rule(self) -> StaggerRule {
+            StaggerRule::new(self.lines, self.tolerance).unwrap()
+        }
+    }
+
+    // A deck small enough to be laid quickly, with a quarter more planks than its area,
+    // so running out of planks is a bug.
+    #[derive(Clone, Debug)]
+    struct SmallDeckForTest {
+        length: usize,
+        width: usize,
+        planks: Vec<usize>,
+    }
+
+    impl Arbitrary for SmallDeckForTest {
+        fn arbitrary(g: &mut Gen) -> Self {
+            let length = usize::arbitrary(g) % 50 + 1;
+            let width = usize::arbitrary(g) % 10 + 1;
+            let mut planks = vec![];
+            while planks.iter().sum::<usize>() < length * width * 5 / 4 {
+                planks.push(usize::arbitrary(g) % length + 1);
+            }
+            SmallDeckForTest { length, width, planks }
+        }
+    }
+
+    impl SmallDeckForTest {
+        fn to_deck(&self) -> Deck {
+            Deck::new(self.length, self.width).unwrap()
+        }
+
+        fn to_plank_heap(&self) -> PlankHeap {
+            PlankHeap::from_planks(self.planks.iter().map(|&length| Plank::new(length).unwrap()).collect())
+        }
+    }
+
+    // The layout, or the verdict when there is none: the planks left not fitting is discarded,
+    // any other error fails.
+    fn laid(result: Result<Calepinage, CalepinageError>, deck: &Deck) -> Result<Calepinage, TestResult> {
+        match result {
+            Ok(Calepinage(lines)) => {
+                let filled = |line: &Line| line.0.iter().map(|plank| plank.length).sum::<usize>() == deck.length;
+                if lines.len() == deck.width && lines.iter().all(filled) {
+                    Ok(Calepinage(lines))
+                } else {
+                    Err(TestResult::error("layout does not fill the deck"))
+                }
+            }
+            Err(CalepinageError::OnlyUnusablePlanksRemaining(_)) => Err(TestResult::discard()),
+            Err(error) => Err(TestResult::error(format!("unexpected error {:?}", error))),
+        }
+    }
+
+    fn follows_stagger(result: Result<Calepinage, CalepinageError>, deck: &Deck, stagger: &StaggerRule) -> TestResult {
+        match laid(result, deck) {
+            Ok(calepinage) => match stagger.find_violation(&calepinage) {
+                Some(_violation) => TestResult::error("found junction repeated too soon"),
+                None => TestResult::passed(),
+            },
+            Err(verdict) => verdict,
+        }
+    }
+
+    fn sorted_lengths(Calepinage(lines): &Calepinage) -> Vec<usize> {
+        let mut lengths: Vec<usize> = lines.iter().flat_map(|line| line.lengths()).collect();
+        lengths.sort_unstable();
+        lengths
+    }
+
+    #[test]
+    fn check_stagger_rule() {
+        QuickCheck::new()
+            .tests(100)
+            .max_tests(1000)
+            .min_tests_passed(100)
+            .quickcheck(
+                junctions_should_not_repeat_within_stagger_lines
+                    as fn(SmallDeckForTest, StaggerRuleForTest) -> TestResult,
+            );
+    }
+
+    fn junctions_should_not_repeat_within_stagger_lines(
+        deck: SmallDeckForTest,
+        stagger: StaggerRuleForTest,
+    ) -> TestResult {
+        let stagger = stagger.to_stagger_rule();
+        let rules = Rules::default().with_stagger(stagger.clone());
+        let result = calepine_with_rules(deck.to_plank_heap(), deck.to_deck(), &rules);
+        follows_stagger(result, &deck.to_deck(), &stagger)
+    }
+
+    #[test]
+    fn should_avoid_staircase_when_rule_is_set() {
+        let deck = Deck::new(9, 3).unwrap();
+        let plank_heap = || PlankHeap::default().add(2, 1).add(3, 3).add(4, 4);
+        let staircase = StaircaseRule::new(3, 0).unwrap();
+
+        let greedy = calepine(plank_heap(), deck.clone()).unwrap();
+        let actual = calepine_with_rules(
+            plank_heap(),
+            deck,
+            &Rules::default().with_staircase(staircase.clone()),
+        );
+
+        assert_that(&staircase.find_staircases(&greedy)).has_length(2);
+        let expected: Calepinage = a_calepinage()
+            .with_line(plank_line![Plank::new(4).unwrap(), Plank::new(4).unwrap(), Plank::new(1).unwrap()])
+            .with_line(plank_line![Plank::new(3).unwrap(), Plank::new(3).unwrap(), Plank::new(3).unwrap()])
+            .with_line(plank_line![Plank::new(1).unwrap(), Plank::new(4).unwrap(), Plank::new(4).unwrap()]);
+        assert_that(&actual).is_ok().is_equal_to(&expected);
+    }
+
+    #[test]
+    fn check_random_layouts() {
+        QuickCheck::new()
+            .tests(100)
+            .max_tests(1000)
+            .min_tests_passed(100)
+            .quickcheck(
+                random_layouts_should_follow_stagger_rule
+                    as fn(SmallDeckForTest, StaggerRuleForTest, u64) -> TestResult,
+            );
+    }
+
+    fn random_layouts_should_follow_stagger_rule(
+        deck: SmallDeckForTest,
+        stagger: StaggerRuleForTest,
+        seed: u64,
+    ) -> TestResult {
+        let stagger = stagger.to_stagger_rule();
+        let rules = Rules::default().with_stagger(stagger.clone());
+        let result = calepine_randomly(deck.to_plank_heap(), deck.to_deck(), &rules, seed);
+        follows_stagger(result, &deck.to_deck(), &stagger)
+    }
+
+    #[test]
+    fn check_beam_layouts() {
+        QuickCheck::new()
+            .tests(100)
+            .max_tests(1000)
+            .min_tests_passed(100)
+            .quickcheck(
+                beam_layouts_should_follow_stagger_rule
+                    as fn(SmallDeckForTest, StaggerRuleForTest) -> TestResult,
+            );
+    }
+
+    fn beam_layouts_should_follow_stagger_rule(deck: SmallDeckForTest, stagger: StaggerRuleForTest) -> TestResult {
+        let stagger = stagger.to_stagger_rule();
+        let rules = Rules::default().with_stagger(stagger.clone());
+        let result = calepine_with_beam(deck.to_plank_heap(), deck.to_deck(), &rules, 3);
+        follows_stagger(result, &deck.to_deck(), &stagger)
+    }
+
+    #[test]
+    fn check_improve_stagger() {
+        QuickCheck::new()
+            .tests(100)
+            .max_tests(1000)
+            .min_tests_passed(100)
+            .quickcheck(
+                improve_stagger_should_keep_planks_and_rules as fn(SmallDeckForTest, u64) -> TestResult,
+            );
+    }
+
+    fn improve_stagger_should_keep_planks_and_rules(deck: SmallDeckForTest, seed: u64) -> TestResult {
+        let rules = Rules::default();
+        let calepinage = match laid(calepine_randomly(deck.to_plank_heap(), deck.to_deck(), &rules, seed), &deck.to_deck()) {
+            Ok(calepinage) => calepinage,
+            Err(verdict) => return verdict,
+        };
+        let improved = improve_stagger(calepinage.clone(), &rules);
+        TestResult::from_bool(
+            sorted_lengths(&improved) == sorted_lengths(&calepinage)
+                && rules.accept(&improved)
+                // no pair of lines to compare is the best stagger
+                && min_stagger(&improved).unwrap_or(usize::MAX) >= min_stagger(&calepinage).unwrap_or(usize::MAX),
+        )
+    }
+
+    #[test]
+    fn improve_stagger_may_leave_no_neighbour_lines_with_junctions() {
+        let calepinage = a_calepinage()
+            .with_line(plank_line![Plank::new(6).unwrap()])
+            .with_line(plank_line![Plank::new(3).unwrap(), Plank::new(3).unwrap()])
+            .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(2).unwrap(), Plank::new(2).unwrap()]);
+
+        let improved = improve_stagger(calepinage.clone(), &Rules::default());
+
+        assert_that(&min_stagger(&calepinage)).is_equal_to(Some(1));
+        assert_that(&min_stagger(&improved)).is_equal_to(None);
+    }
+
+    #[test]
+    fn check_anneal() {
+        QuickCheck::new()
+            .tests(100)
+            .max_tests(1000)
+            .min_tests_passed(100)
+            .quickcheck(anneal_should_keep_planks_and_rules as fn(SmallDeckForTest, u64) -> TestResult);
+    }
+
+    fn anneal_should_keep_planks_and_rules(deck: SmallDeckForTest, seed: u64) -> TestResult {
+        let rules = Rules::default();
+        let calepinage = match laid(calepine_randomly(deck.to_plank_heap(), deck.to_deck(), &rules, seed), &deck.to_deck()) {
+            Ok(calepinage) => calepinage,
+            Err(verdict) => return verdict,
+        };
+        let score = |calepinage: &Calepinage| repetition(calepinage) - min_stagger(calepinage).unwrap_or(0) as f64;
+        let annealed = anneal(calepinage.clone(), &rules, &score, seed, 200);
+        TestResult::from_bool(
+            sorted_lengths(&annealed) == sorted_lengths(&calepinage)
+                && rules.accept(&annealed)
+                && score(&annealed) <= score(&calepinage),
+        )
+    }
+
+    #[test]
+    fn same_seed_should_give_same_layout() {
+        let deck = Deck::new(10, 4).unwrap();
+        let plank_heap = || PlankHeap::default().add(6, 3).add(6, 4).add(6, 5);
+
+        let first = calepine_randomly(plank_heap(), deck.clone(), &Rules::default(), 12);
+        let second = calepine_randomly(plank_heap(), deck, &Rules::default(), 12);
+
+        assert_that(&first).is_ok();
+        assert_that(&first).is_equal_to(&second);
+    }
+
+    #[test]
+    fn random_candidates_should_be_distinct_and_reproducible() {
+        let deck = Deck::new(10, 4).unwrap();
+        let plank_heap = PlankHeap::default().add(6, 3).add(6, 4).add(6, 5);
+
+        let candidates = random_candidates(&plank_heap, &deck, &Rules::default(), 3, 4);
+
+        assert_that(&candidates).has_length(4);
+        for (index, (seed, calepinage)) in candidates.iter().enumerate() {
+            assert!(candidates[..index].iter().all(|(_, other)| other != calepinage));
+            assert_that(&calepine_randomly(plank_heap.clone(), deck.clone(), &Rules::default(), *seed))
+                .is_ok()
+                .is_equal_to(calepinage);
+        }
+    }
+
+    #[test]
+    fn should_not_repeat_line_when_rule_is_set() {
+        let deck = Deck::new(4, 2).unwrap();
+        let plank_heap = || PlankHeap::default().add(2, 4).add(2, 2);
+
+        let greedy = calepine(plank_heap(), deck.clone()).unwrap();
+        let actual = calepine_with_rules(
+            plank_heap(),
+            deck,
+            &Rules::default().with_repetition(RepetitionRule::new(1).unwrap()),
+        );
+
+        assert_that(&repetition(&greedy)).is_equal_to(1.0);
+        let expected: Calepinage = a_calepinage()
+            .with_line(plank_line![Plank::new(4).unwrap()])
+            .with_line(plank_line![Plank::new(2).unwrap(), Plank::new(2).unwrap()]);
+        assert_that(&actual).is_ok().is_equal_to(&expected);
+    }
+
+    #[test]
+    fn should_fail_when_every_line_would_be_repeated() {
+        let deck = Deck::new(4, 2).unwrap();
+        let plank_heap = PlankHeap::default().add(2, 4);
+
+        let actual = calepine_with_rules(
+            plank_heap,
+            deck,
+            &Rules::default().with_repetition(RepetitionRule::new(1).unwrap()),
+        );
+
+        assert_that(&actual)
+            .is_err()
+            .is_equal_to(CalepinageError::RepeatedLine(1));
+    }
+
     #[test]
     fn failing_pbt_test_case_2() {
         let deck = Deck {
@@ -291,10 +589,10 @@ mod calepinage_test {
         };
         let plank_heap = PlankHeap::from_planks(
             vec![
-                Plank { length: 10 },
-                Plank { length: 10 },
-                Plank { length: 2 },
-                Plank { length: 2 },
+                Plank::new(10).unwrap(),
+                Plank::new(10).unwrap(),
+                Plank::new(2).unwrap(),
+                Plank::new(2).unwrap(),
             ], //
         );
         let result = calepine(plank_heap, deck);
@@ -321,12 +619,12 @@ mod calepinage_test {
         };
         let plank_heap = PlankHeap::from_planks(
             vec![
-                Plank { length: 10 },
-                Plank { length: 10 },
-                Plank { length: 10 },
-                Plank { length: 2 },
-                Plank { length: 2 },
-                Plank { length: 2 },
+                Plank::new(10).unwrap(),
+                Plank::new(10).unwrap(),
+                Plank::new(10).unwrap(),
+                Plank::new(2).unwrap(),
+                Plank::new(2).unwrap(),
+                Plank::new(2).unwrap(),
             ], //
         );
         let result = calepine(plank_heap, deck);